@@ -0,0 +1,93 @@
+#!/usr/bin/env python3
+"""Экспорт артефактов TopicModelV42 (pkl) в переносимый JSON для Rust-бэкенда.
+
+Usage: python export_artifacts.py v42_artifacts.pkl v42_artifacts.json
+"""
+import json
+import sys
+from dataclasses import asdict
+
+import joblib
+import numpy as np
+
+from nonlinear_topic_clustering_v4_2 import Config
+
+
+def _to_list(x):
+    if x is None:
+        return []
+    return np.asarray(x, dtype=np.float32).tolist()
+
+
+def _keys_to_str(d):
+    return {str(k): v for k, v in (d or {}).items()}
+
+
+# Имена полей `cfg` в pkl -> имена параметров отбора в бэкенде
+CFG_FIELDS = {
+    "delta_base": "topk_delta_base",
+    "delta_long": "topk_delta_long",
+    "long_threshold_chars": "min_len_for_3",
+}
+
+
+def export_config(cfg) -> dict:
+    """Параметры отбора топиков из `cfg` артефактов; недостающие — значения `Config` по умолчанию"""
+    if cfg is None:
+        return asdict(Config())
+    if not isinstance(cfg, dict):
+        cfg = vars(cfg)
+    out = asdict(Config())
+    for key, value in cfg.items():
+        key = CFG_FIELDS.get(key, key)
+        if key in out:
+            out[key] = value
+    return out
+
+
+def export(art: dict) -> dict:
+    tfidf = art["tfidf"]
+    svd = art["svd"]
+    # Обученный TfidfVectorizer/TruncatedSVD; заглушка модели их не содержит
+    if not hasattr(tfidf, "vocabulary_") or not hasattr(tfidf, "idf_"):
+        raise ValueError("artifacts contain no fitted TF-IDF (`tfidf.vocabulary_` is missing); retrain the model")
+    if not hasattr(svd, "components_"):
+        raise ValueError("artifacts contain no fitted SVD (`svd.components_` is missing); retrain the model")
+
+    return {
+        "config": export_config(art.get("cfg")),
+        "tfidf": {
+            "vocabulary": {str(t): int(i) for t, i in tfidf.vocabulary_.items()},
+            "idf": _to_list(tfidf.idf_),
+            "lowercase": bool(tfidf.lowercase),
+            "token_pattern": tfidf.token_pattern,
+            "ngram_range": list(tfidf.ngram_range),
+            "sublinear_tf": bool(tfidf.sublinear_tf),
+            "norm": tfidf.norm,
+        },
+        "svd": {"components": _to_list(svd.components_)},
+        "centroids": _to_list(art["centroids"]),
+        "topic_ids": [int(t) for t in art["topic_ids"]],
+        "topic_names": _keys_to_str(art["topic_names"]),
+        "topic_terms": _keys_to_str(art["topic_terms"]),
+        "overlap_min": float(art["overlap_min"]),
+        "tau2_global": float(art["tau2_global"]),
+        "tau3_global": float(art["tau3_global"]),
+        "tau2_per": {str(k): float(v) for k, v in (art["tau2_per"] or {}).items()},
+        "tau3_per": {str(k): float(v) for k, v in (art["tau3_per"] or {}).items()},
+        "cluster2gold": _keys_to_str(art["cluster2gold"]),
+    }
+
+
+if __name__ == "__main__":
+    src = sys.argv[1] if len(sys.argv) > 1 else "v42_artifacts.pkl"
+    dst = sys.argv[2] if len(sys.argv) > 2 else "v42_artifacts.json"
+
+    art = joblib.load(src)
+    try:
+        out = export(art)
+    except ValueError as e:
+        sys.exit(f"❌ {src}: {e}")
+    with open(dst, "w", encoding="utf-8") as f:
+        json.dump(out, f, ensure_ascii=False)
+    print(f"✅ Артефакты экспортированы → {dst}")
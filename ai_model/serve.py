@@ -2,7 +2,7 @@ from fastapi import FastAPI
 from pydantic import BaseModel
 from typing import List
 
-from simple_predict import predict_texts
+from simple_predict import RulesFile, predict_texts
 
 
 class PredictSample(BaseModel):
@@ -15,11 +15,13 @@ class PredictRequest(BaseModel):
 
 
 app = FastAPI()
+# Правила читаются при старте и перечитываются, только если файл изменился
+rules = RulesFile()
 
 
 @app.post("/predict")
 def predict(req: PredictRequest):
     items = [s.model_dump() for s in req.data]
-    return predict_texts(items)
+    return predict_texts(items, rules.current())
 
 
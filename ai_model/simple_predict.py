@@ -1,65 +1,233 @@
 #!/usr/bin/env python3
 import json
+import math
+import os
+import re
 import sys
-from typing import List, Dict, Any
-
-
-def predict_texts(items: List[Dict[str, Any]]) -> Dict[str, Any]:
-    def classify(text: str) -> Dict[str, Any]:
-        tl = text.lower()
-
-        topics = []
-        def push_if(kw: str, topic: str):
-            if kw in tl and topic not in topics:
-                topics.append(topic)
-
-        push_if("обслужив", "Обслуживание")
-        push_if("мобильное прилож", "Мобильное приложение")
-        push_if("онлайн-банк", "Онлайн-банк")
-        push_if("сайт", "Сайт")
-        push_if("ипотек", "Ипотека")
-        push_if("кредит", "Кредит")
-        push_if("карт", "Карта")
-        push_if("терминал", "Терминал")
-        push_if("поддержк", "Поддержка")
-
-        if not topics:
-            topics.append("Обслуживание")
-
-        has_explicit_pos = "положительно" in tl
-        has_explicit_neg = "отрицательно" in tl
-        has_explicit_neu = "нейтрально" in tl
-
-        neg_kw = [
-            "непонрав", "не понрав", "зависает", "зависа", "долго", "плохо", "ужасн",
-            "медлен", "лома", "обман",
-        ]
-        pos_kw = [
-            "понрав", "нрав", "быстро", "отлично", "хорошо", "рекоменд", "удоб",
-        ]
-
-        has_neg = has_explicit_neg or any(k in tl for k in neg_kw)
-        has_pos = has_explicit_pos or any(k in tl for k in pos_kw)
-
-        if has_neg:
-            sent = "отрицательно"
-        elif has_pos:
-            sent = "положительно"
-        elif has_explicit_neu:
-            sent = "нейтрально"
-        else:
-            sent = "нейтрально"
-
-        return {
-            "topics": topics,
-            "sentiments": [sent for _ in topics],
-        }
+from typing import List, Dict, Any, Optional, Tuple
 
+import yaml
+
+
+# Те же правила ключевых слов, что и у бэкенда (формат описан в rules.yaml):
+# в образе файл лежит рядом со скриптом, в репозитории берётся из исходников бэкенда
+HERE = os.path.dirname(os.path.abspath(__file__))
+DEFAULT_RULES_PATHS = [
+    os.path.join(HERE, "rules.yaml"),
+    os.path.join(HERE, "..", "backend", "src", "predict", "rules.yaml"),
+]
+SENTENCE_BREAKS = ".!?\n"
+CLAUSE_BREAKS = ",;:"
+SENTIMENT_LABELS = {"positive": "положительно", "neutral": "нейтрально", "negative": "отрицательно"}
+FLIPPED = {"positive": "negative", "negative": "positive", "neutral": "neutral"}
+POLARITY = {"positive": 1.0, "neutral": 0.0, "negative": -1.0}
+
+
+def rules_path(path: Optional[str] = None) -> str:
+    path = path or os.environ.get("RULES_PATH")
+    if path:
+        return path
+    for candidate in DEFAULT_RULES_PATHS:
+        if os.path.exists(candidate):
+            return candidate
+    raise FileNotFoundError("rules.yaml not found, set RULES_PATH")
+
+
+def load_rules(path: Optional[str] = None) -> Dict[str, Any]:
+    path = rules_path(path)
+    with open(path, encoding="utf-8") as f:
+        spec = json.load(f) if path.lower().endswith(".json") else yaml.safe_load(f)
+
+    negation = spec.get("negation") or {}
+    scoring = spec.get("scoring") or {}
+    contrast = scoring.get("contrast") or {}
+    rules = []
+    for rule in spec["rules"]:
+        phrase = lambda s: r"\s+".join(re.escape(w) for w in s.split())
+        alternatives = [r"\b" + phrase(s) + r"\w*" for s in rule.get("stems", [])]
+        alternatives += [r"\b" + phrase(w) + r"\b" for w in rule.get("words", [])]
+        alternatives += ["(?:" + r + ")" for r in rule.get("regex", [])]
+        sentiment = rule.get("sentiment")
+        rules.append({
+            "id": rule["id"],
+            "pattern": re.compile("|".join(alternatives), re.IGNORECASE),
+            "topic": rule.get("topic"),
+            "sentiment": sentiment,
+            "priority": rule.get("priority", 0),
+            "weight": rule.get("weight", 1.0),
+            "on_negation": rule.get("on_negation") or ("flip" if sentiment else "keep"),
+        })
+    contrast_words = [re.escape(w.strip()) for w in contrast.get("words", [])]
+    return {
+        "rules": rules,
+        "particles": {p.lower() for p in negation.get("particles", ["не", "нет", "ни", "без"])},
+        "window": negation.get("window", 3),
+        "negation_scale": negation.get("scale", 0.75),
+        "intensifiers": {w.lower(): k for w, k in (scoring.get("intensifiers") or {}).items()},
+        "contrast": re.compile(r"\b(?:" + "|".join(contrast_words) + r")\b", re.IGNORECASE) if contrast_words else None,
+        "contrast_before": contrast.get("before", 1.0),
+        "contrast_after": contrast.get("after", 1.0),
+        "neutral_threshold": scoring.get("neutral_threshold", 0.0),
+        "default_topic": spec.get("default_topic"),
+    }
+
+
+class RulesFile:
+    """Правила из файла, перечитываются при изменении mtime; при ошибке остаются прежние"""
+
+    def __init__(self, path: Optional[str] = None):
+        self.path = rules_path(path)
+        self.mtime = os.path.getmtime(self.path)
+        self.rules = load_rules(self.path)
+
+    def current(self) -> Dict[str, Any]:
+        try:
+            mtime = os.path.getmtime(self.path)
+            if mtime != self.mtime:
+                # Сломанный файл не перечитывается на каждом запросе, только после следующего изменения
+                self.mtime = mtime
+                self.rules = load_rules(self.path)
+        except Exception as e:
+            print(f"failed to reload rules from {self.path}: {e}", file=sys.stderr)
+        return self.rules
+
+
+def spans(text: str, start: int, end: int, breaks: str) -> List[Tuple[int, int]]:
+    """Непустые куски text[start:end] между разделителями"""
+    result = []
+    begin = start
+    for i in range(start, end):
+        if text[i] in breaks:
+            if text[begin:i].strip():
+                result.append(trimmed(text, begin, i))
+            begin = i + 1
+    if text[begin:end].strip():
+        result.append(trimmed(text, begin, end))
+    return result
+
+
+def trimmed(text: str, start: int, end: int) -> Tuple[int, int]:
+    piece = text[start:end]
+    return start + len(piece) - len(piece.lstrip()), start + len(piece.rstrip())
+
+
+def clauses(rules: Dict[str, Any], text: str) -> List[List[Any]]:
+    """Фразы [start, end, предложение, вес, часть предложения между союзами]:
+    в предложении с противительным союзом фразы до него ослабляются, после — усиливаются"""
+    result = []
+    segment = 0
+    for sentence, (sentence_start, sentence_end) in enumerate(spans(text, 0, len(text), SENTENCE_BREAKS)):
+        first = len(result)
+        contrast_at = None
+        if first > 0:
+            segment += 1
+        for start, end in spans(text, sentence_start, sentence_end, CLAUSE_BREAKS):
+            clause_start = start
+            matches = rules["contrast"].finditer(text, start, end) if rules["contrast"] else []
+            for m in matches:
+                if text[clause_start:m.start()].strip():
+                    result.append([*trimmed(text, clause_start, m.start()), sentence, 1.0, segment])
+                clause_start = m.start()
+                contrast_at = len(result)
+                segment += 1
+            result.append([clause_start, end, sentence, 1.0, segment])
+        if contrast_at is not None:
+            for i in range(first, len(result)):
+                result[i][3] = rules["contrast_before"] if i < contrast_at else rules["contrast_after"]
+    return result
+
+
+def modifiers(rules: Dict[str, Any], before: str) -> Tuple[bool, float]:
+    """Отрицание и множитель усилителей среди последних window слов; усилитель после отрицания не учитывается"""
+    negated, intensity = False, 1.0
+    words = re.findall(r"\w+", before)
+    for word in reversed(words[-rules["window"]:] if rules["window"] else []):
+        word = word.lower()
+        if word in rules["particles"]:
+            negated, intensity = True, 1.0
+        elif word in rules["intensifiers"]:
+            intensity *= rules["intensifiers"][word]
+    return negated, intensity
+
+
+def judge(rules: Dict[str, Any], scores: List[float]) -> str:
+    if not scores:
+        return "neutral"
+    total = sum(scores)
+    # Сумма вкладов сжимается в (-1, 1), как в бэкенде
+    score = total / math.sqrt(total * total + 1.0)
+    if score > 0 and score >= rules["neutral_threshold"]:
+        return "positive"
+    if score < 0 and score <= -rules["neutral_threshold"]:
+        return "negative"
+    return "neutral"
+
+
+def topic_clauses(parts: List[List[Any]], hits: List[Dict[str, Any]], topic: str) -> Optional[set]:
+    """Фразы топика: фраза без топика относится к ближайшей предыдущей фразе с топиком (в начале — к следующей)
+    той же части предложения между союзами; без такой фразы в предложении с топиками не учитывается,
+    предложение без топиков — общее для всех; None — топик не упомянут"""
+    with_topic = {h["clause"] for h in hits if h["topic"]}
+    mentioned = {h["clause"] for h in hits if h["topic"] == topic}
+    if not mentioned - {None}:
+        return None
+    owned = set()
+    for i, clause in enumerate(parts):
+        segment = [j for j, c in enumerate(parts) if c[4] == clause[4]]
+        before = [j for j in segment if j <= i and j in with_topic]
+        after = [j for j in segment if j > i and j in with_topic]
+        owner = before[-1] if before else (after[0] if after else None)
+        if owner is None:
+            if not any(c[2] == clause[2] and j in with_topic for j, c in enumerate(parts)):
+                owned.add(i)
+        elif owner in mentioned:
+            owned.add(i)
+    return owned
+
+
+def classify(rules: Dict[str, Any], text: str) -> Dict[str, Any]:
+    parts = clauses(rules, text)
+    hits = []
+    for rule in rules["rules"]:
+        for m in rule["pattern"].finditer(text):
+            clause = next((i for i, c in enumerate(parts) if c[0] <= m.start() < c[1]), None)
+            clause_start, clause_weight = (parts[clause][0], parts[clause][3]) if clause is not None else (m.start(), 1.0)
+            negated, intensity = modifiers(rules, text[clause_start:m.start()])
+            if negated and rule["on_negation"] == "skip":
+                continue
+            score = None
+            if rule["sentiment"]:
+                negation = -rules["negation_scale"] if negated and rule["on_negation"] == "flip" else 1.0
+                score = POLARITY[rule["sentiment"]] * rule["weight"] * intensity * negation * clause_weight
+            hits.append({"clause": clause, "topic": rule["topic"], "priority": rule["priority"], "score": score})
+
+    topics = []
+    for hit in hits:
+        if hit["topic"] and all(t != hit["topic"] for _, t in topics):
+            topics.append((hit["priority"], hit["topic"]))
+    # Стабильная сортировка сохраняет порядок файла при равном приоритете
+    topics = [t for _, t in sorted(topics, key=lambda p: p[0], reverse=True)]
+    if not topics and rules["default_topic"]:
+        topics.append(rules["default_topic"])
+
+    # Тональность топика — по фразам, которые к нему относятся
+    sentiments = []
+    for topic in topics:
+        owned = topic_clauses(parts, hits, topic)
+        scores = [h["score"] for h in hits if h["score"] is not None and (owned is None or h["clause"] is None or h["clause"] in owned)]
+        sentiments.append(SENTIMENT_LABELS[judge(rules, scores)])
+    return {
+        "topics": topics,
+        "sentiments": sentiments,
+    }
+
+
+def predict_texts(items: List[Dict[str, Any]], rules: Dict[str, Any]) -> Dict[str, Any]:
     preds = []
     for it in items:
         cid = int(it.get("id"))
         text = str(it.get("text", ""))
-        cls = classify(text)
+        cls = classify(rules, text)
         preds.append({
             "id": cid,
             "topics": cls["topics"],
@@ -83,7 +251,7 @@ if __name__ == "__main__":
     else:
         data = json.load(sys.stdin)
 
-    out = predict_texts(data.get("data", []))
+    out = predict_texts(data.get("data", []), load_rules())
     print(json.dumps(out, ensure_ascii=False))
 
 
@@ -0,0 +1,27 @@
+#!/usr/bin/env python3
+# Запуск: python -m unittest test_simple_predict (из каталога ai_model)
+import json
+import os
+import unittest
+
+from simple_predict import classify, load_rules
+
+
+BACKEND_PREDICT = os.path.join(os.path.dirname(os.path.abspath(__file__)), "..", "backend", "src", "predict")
+
+
+class SharedCasesTest(unittest.TestCase):
+    """Те же примеры проверяет бэкенд (predict/rules.rs): ответы serve.py и Rust совпадают"""
+
+    def test_matches_backend(self):
+        rules = load_rules(os.path.join(BACKEND_PREDICT, "rules.yaml"))
+        with open(os.path.join(BACKEND_PREDICT, "rules_cases.json"), encoding="utf-8") as f:
+            cases = json.load(f)
+        for case in cases:
+            with self.subTest(text=case["text"]):
+                expected = {"topics": case["topics"], "sentiments": case["sentiments"]}
+                self.assertEqual(classify(rules, case["text"]), expected)
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -1,74 +1,584 @@
-use actix_web::{get, post, web, Responder};
+use std::collections::{HashMap, HashSet};
 
+use actix_multipart::Multipart;
+use actix_web::error::{ErrorBadRequest, ErrorConflict, ErrorInternalServerError, ErrorNotFound, ErrorPayloadTooLarge};
+use actix_web::http::header::ContentDisposition;
+use actix_web::web::Bytes;
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder, ResponseError};
+use futures_util::{stream, StreamExt, TryStreamExt};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::config::Config;
 use crate::domain::*;
-use crate::predict::Predictor;
+use crate::jobs::JobManager;
+use crate::metrics;
+use crate::predict::reload::{ModelReloader, ReloadablePredictor};
+use crate::predict::rules::SharedRules;
+use crate::predict::{PredictOutput, Predictor};
+use crate::storage::{NewReview, ReviewFilter, ReviewStore};
+use crate::taxonomy::{SharedTaxonomy, TaxonomyError};
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(get_topics)
         .service(get_topics_stats)
+        .service(get_topic)
+        .service(post_topic)
+        .service(put_topic)
+        .service(delete_topic)
         .service(get_topic_timeline)
         .service(get_reviews)
-        .service(post_predict);
+        .service(post_predict)
+        .service(post_upload)
+        .service(get_download)
+        .service(post_job)
+        .service(get_job)
+        .service(get_job_result)
+        .service(delete_job)
+        .service(healthz)
+        .service(readyz)
+        .service(get_model_info)
+        .service(get_models)
+        .service(get_shadow_stats)
+        .service(get_shadow_disagreements)
+        .service(get_metrics)
+        .service(get_rules)
+        .service(post_rules_explain)
+        .service(post_admin_reload)
+        .service(post_admin_rules_reload);
+}
+
+/// Выполняет запрос к хранилищу в пуле блокирующих задач
+async fn with_store<T, F>(store: web::Data<ReviewStore>, f: F) -> Result<T, actix_web::Error>
+where
+    F: FnOnce(&ReviewStore) -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    web::block(move || f(&store)).await?.map_err(|e| {
+        error!("Review store query failed: {:?}", e);
+        ErrorInternalServerError("storage error")
+    })
+}
+
+/// Изменяет таксономию в хранилище и подменяет снимок, по которому предикторы разрешают метки
+async fn change_taxonomy<T, F>(
+    store: web::Data<ReviewStore>,
+    taxonomy: web::Data<SharedTaxonomy>,
+    f: F,
+) -> Result<T, actix_web::Error>
+where
+    F: FnOnce(&ReviewStore) -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let result = web::block(move || {
+        let _updates = taxonomy.lock_updates();
+        let result = f(&store)?;
+        taxonomy.replace(store.taxonomy()?);
+        Ok(result)
+    })
+    .await?;
+    result.map_err(|e: anyhow::Error| match e.downcast::<TaxonomyError>() {
+        Ok(e) => e.into(),
+        Err(e) => {
+            error!("Topic taxonomy update failed: {:?}", e);
+            ErrorInternalServerError("storage error")
+        }
+    })
+}
+
+/// Запросы к `/admin/*` и изменения таксономии требуют `admin_token`, если он задан
+fn authorize_admin(req: &actix_web::HttpRequest, config: &Config) -> Result<(), actix_web::Error> {
+    let Some(token) = &config.admin_token else { return Ok(()) };
+    let authorized = req
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|v| v == token);
+    if authorized { Ok(()) } else { Err(actix_web::error::ErrorUnauthorized("invalid admin token")) }
 }
 
 #[get("/topics")]
-async fn get_topics() -> impl Responder {
-    let topics = vec![
-        Topic { id: 1, name: "Ипотека".into() },
-        Topic { id: 2, name: "Карты".into() },
-        Topic { id: 3, name: "Кредиты".into() },
-        Topic { id: 4, name: "Вклады".into() },
-    ];
+async fn get_topics(taxonomy: web::Data<SharedTaxonomy>, query: web::Query<TopicsQuery>) -> impl Responder {
+    let include_inactive = query.include_inactive.unwrap_or(false);
+    let taxonomy = taxonomy.current();
+    let topics: Vec<&Topic> = taxonomy.topics().iter().filter(|t| include_inactive || t.active).collect();
     web::Json(serde_json::json!({ "topics": topics }))
 }
 
+#[get("/topics/{topic_id}")]
+async fn get_topic(taxonomy: web::Data<SharedTaxonomy>, path: web::Path<i32>) -> Result<impl Responder, actix_web::Error> {
+    let topic_id = path.into_inner();
+    let topic = taxonomy.current().get(topic_id).cloned().ok_or(TaxonomyError::NotFound(topic_id))?;
+    Ok(web::Json(topic))
+}
+
+#[post("/topics")]
+async fn post_topic(
+    req: actix_web::HttpRequest,
+    config: web::Data<Config>,
+    store: web::Data<ReviewStore>,
+    taxonomy: web::Data<SharedTaxonomy>,
+    payload: web::Json<TopicInput>,
+) -> Result<HttpResponse, actix_web::Error> {
+    authorize_admin(&req, &config)?;
+    let input = payload.into_inner();
+    let topic = change_taxonomy(store, taxonomy, move |s| s.create_topic(&input)).await?;
+    info!("Topic {} ({}) created", topic.id, topic.name);
+    Ok(HttpResponse::Created().json(topic))
+}
+
+#[put("/topics/{topic_id}")]
+async fn put_topic(
+    req: actix_web::HttpRequest,
+    config: web::Data<Config>,
+    store: web::Data<ReviewStore>,
+    taxonomy: web::Data<SharedTaxonomy>,
+    path: web::Path<i32>,
+    payload: web::Json<TopicInput>,
+) -> Result<HttpResponse, actix_web::Error> {
+    authorize_admin(&req, &config)?;
+    let (topic_id, input) = (path.into_inner(), payload.into_inner());
+    let topic = change_taxonomy(store, taxonomy, move |s| s.update_topic(topic_id, &input)).await?;
+    info!("Topic {} ({}) updated", topic.id, topic.name);
+    Ok(HttpResponse::Ok().json(topic))
+}
+
+#[delete("/topics/{topic_id}")]
+async fn delete_topic(
+    req: actix_web::HttpRequest,
+    config: web::Data<Config>,
+    store: web::Data<ReviewStore>,
+    taxonomy: web::Data<SharedTaxonomy>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, actix_web::Error> {
+    authorize_admin(&req, &config)?;
+    let topic_id = path.into_inner();
+    change_taxonomy(store, taxonomy, move |s| s.delete_topic(topic_id)).await?;
+    info!("Topic {} deleted", topic_id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Статистика по топикам: плоским списком или деревом (`rollup=true`, `depth`)
 #[get("/topics/stats")]
-async fn get_topics_stats(query: web::Query<StatsQuery>) -> impl Responder {
+async fn get_topics_stats(
+    store: web::Data<ReviewStore>,
+    taxonomy: web::Data<SharedTaxonomy>,
+    query: web::Query<StatsQuery>,
+) -> Result<impl Responder, actix_web::Error> {
+    let query = query.into_inner();
     let period = Period { from: query.date_from.clone(), to: query.date_to.clone() };
-    let topics = vec![
-        TopicsStatsItem { id: 1, name: "Ипотека".into(), stats: SentimentStats { positive: 120, neutral: 45, negative: 35 }},
-        TopicsStatsItem { id: 2, name: "Карты".into(), stats: SentimentStats { positive: 200, neutral: 80, negative: 60 }},
-    ];
-    web::Json(TopicsStatsResponse { period, topics })
+    let topics = if query.rollup.unwrap_or(false) {
+        let labels = with_store(store, move |s| {
+            s.topic_review_sentiments(&query.date_from, &query.date_to, query.region.as_deref())
+        })
+        .await?;
+        taxonomy.current().rollup_stats(&labels, query.depth)
+    } else {
+        with_store(store, move |s| s.topic_stats(&query.date_from, &query.date_to, query.region.as_deref())).await?
+    };
+    Ok(web::Json(TopicsStatsResponse { period, topics }))
 }
 
 #[get("/topics/{topic_id}/timeline")]
-async fn get_topic_timeline(path: web::Path<i32>, query: web::Query<TimelineQuery>) -> impl Responder {
+async fn get_topic_timeline(
+    store: web::Data<ReviewStore>,
+    taxonomy: web::Data<SharedTaxonomy>,
+    path: web::Path<i32>,
+    query: web::Query<TimelineQuery>,
+) -> Result<impl Responder, actix_web::Error> {
     let topic_id = path.into_inner();
-    let topic = match topic_id {
-        1 => Topic { id: 1, name: "Ипотека".into() },
-        2 => Topic { id: 2, name: "Карты".into() },
-        3 => Topic { id: 3, name: "Кредиты".into() },
-        4 => Topic { id: 4, name: "Вклады".into() },
-        _ => Topic { id: topic_id, name: format!("Топик {}", topic_id) },
-    };
-    let _group_by = &query.group_by;
-    let timeline = vec![
-        TimelinePoint { date: "2024-01-01".into(), positive: 5, neutral: 2, negative: 1 },
-        TimelinePoint { date: "2024-01-02".into(), positive: 8, neutral: 3, negative: 4 },
-        TimelinePoint { date: "2024-01-03".into(), positive: 12, neutral: 5, negative: 2 },
-    ];
-    web::Json(TimelineResponse { topic, timeline })
+    let query = query.into_inner();
+    if !matches!(query.group_by.as_str(), "day" | "week" | "month") {
+        return Err(ErrorBadRequest("group_by must be one of: day, week, month"));
+    }
+
+    let taxonomy = taxonomy.current();
+    let topic = taxonomy.get(topic_id).cloned().ok_or(TaxonomyError::NotFound(topic_id))?;
+    let topic_ids = if query.rollup.unwrap_or(false) { taxonomy.subtree(topic_id) } else { vec![topic_id] };
+    let timeline = with_store(store, move |s| {
+        s.timeline(&topic_ids, &query.date_from, &query.date_to, &query.group_by, query.region.as_deref())
+    })
+    .await?;
+    Ok(web::Json(TimelineResponse { topic, timeline }))
 }
 
 #[get("/reviews")]
-async fn get_reviews(query: web::Query<ReviewsQuery>) -> impl Responder {
+async fn get_reviews(store: web::Data<ReviewStore>, query: web::Query<ReviewsQuery>) -> Result<impl Responder, actix_web::Error> {
+    let query = query.into_inner();
+    let sentiment = match query.sentiment.as_deref() {
+        Some(label) => Some(Sentiment::from_label(label).ok_or_else(|| ErrorBadRequest(format!("unknown sentiment {:?}", label)))?),
+        None => None,
+    };
     let period = match (query.date_from.clone(), query.date_to.clone()) {
         (Some(from), Some(to)) => Some(Period { from, to }),
         _ => None,
     };
-    let filters = ReviewsFilters { topic_id: query.topic_id, sentiment: query.sentiment.clone(), period };
-    let pagination = Pagination { page: query.page.unwrap_or(1), limit: query.limit.unwrap_or(20), total: 247 };
-    let reviews = vec![
-        ReviewItem { id: 9321, date: "2024-01-03".into(), sentiment: "negative".into(), text: "Очень долго оформляется ипотека!".into(), region: "Москва".into() },
-        ReviewItem { id: 9322, date: "2024-01-03".into(), sentiment: "negative".into(), text: "Банк затянул с одобрением заявки.".into(), region: "Санкт-Петербург".into() },
-    ];
-    web::Json(ReviewsResponse { filters, pagination, reviews })
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+
+    let filter = ReviewFilter {
+        topic_id: query.topic_id,
+        sentiment,
+        date_from: query.date_from,
+        date_to: query.date_to,
+        region: query.region,
+        page,
+        limit,
+    };
+    let (reviews, total) = with_store(store, move |s| s.reviews(&filter)).await?;
+
+    let filters = ReviewsFilters { topic_id: query.topic_id, sentiment: sentiment.map(|s| s.as_str().to_string()), period };
+    let pagination = Pagination { page, limit, total };
+    Ok(web::Json(ReviewsResponse { filters, pagination, reviews }))
+}
+
+/// Неизвестная версия в `?model=` — 404 в формате ошибок предсказания
+fn unknown_model(name: Option<&str>) -> HttpResponse {
+    let message = format!("unknown model {:?}, see GET /models", name.unwrap_or_default());
+    HttpResponse::NotFound().json(PredictResponse::<PredictItem> {
+        predictions: Vec::new(),
+        errors: vec![PredictErrorItem { id: None, code: "unknown_model".to_string(), message }],
+    })
+}
+
+/// Формат ответа: `?format=` или `legacy_predict_response` из конфигурации
+fn response_format(requested: Option<ResponseFormat>, config: &Config) -> ResponseFormat {
+    requested.unwrap_or(if config.legacy_predict_response { ResponseFormat::Legacy } else { ResponseFormat::Structured })
+}
+
+fn predict_response(
+    status: actix_web::http::StatusCode,
+    format: ResponseFormat,
+    predictions: Vec<PredictItem>,
+    errors: Vec<PredictErrorItem>,
+) -> HttpResponse {
+    match format {
+        ResponseFormat::Structured => HttpResponse::build(status).json(PredictResponse { predictions, errors }),
+        ResponseFormat::Legacy => HttpResponse::build(status).json(PredictResponse {
+            predictions: predictions.into_iter().map(LegacyPredictItem::from).collect(),
+            errors,
+        }),
+    }
 }
 
 #[post("/predict")]
-async fn post_predict(predictor: web::Data<dyn Predictor>, payload: web::Json<PredictRequest>) -> impl Responder {
-    let preds = predictor.predict(&payload.data).await;
-    web::Json(PredictResponse { predictions: preds })
+async fn post_predict(
+    models: web::Data<ReloadablePredictor>,
+    config: web::Data<Config>,
+    query: web::Query<PredictQuery>,
+    payload: web::Json<PredictRequest>,
+) -> HttpResponse {
+    let Some(predictor) = models.get(query.model.as_deref()) else {
+        return unknown_model(query.model.as_deref());
+    };
+    let format = response_format(query.format, &config);
+    match predictor.predict(&payload.data).await {
+        Ok(mut output) => {
+            output.limit_rejected(query.top_k.unwrap_or(0));
+            metrics::record_labels(&output.items);
+            // Ни одного успешного отзыва — отвечаем статусом первой ошибки
+            let status = match output.errors.first() {
+                Some((_, e)) if output.items.is_empty() => e.status_code(),
+                _ => actix_web::http::StatusCode::OK,
+            };
+            let errors = output.error_items();
+            predict_response(status, format, output.items, errors)
+        }
+        Err(e) => {
+            error!("Prediction of {} samples failed: {}", payload.data.len(), e);
+            predict_response(e.status_code(), format, Vec::new(), vec![e.to_item(None)])
+        }
+    }
+}
+
+/// Читает поле `file` из multipart-запроса: имя файла и содержимое
+async fn read_upload_file(payload: &mut Multipart, limit: usize) -> Result<(String, Vec<u8>), actix_web::Error> {
+    while let Some(mut field) = payload.try_next().await? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let file_name = field
+            .content_disposition()
+            .and_then(|cd| cd.get_filename())
+            .unwrap_or("upload.json")
+            .to_string();
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.try_next().await? {
+            if bytes.len() + chunk.len() > limit {
+                return Err(ErrorPayloadTooLarge(format!("file exceeds {} bytes", limit)));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        return Ok((file_name, bytes));
+    }
+    Err(ErrorBadRequest("multipart field `file` is missing"))
+}
+
+#[post("/upload")]
+async fn post_upload(
+    predictor: web::Data<dyn Predictor>,
+    store: web::Data<ReviewStore>,
+    taxonomy: web::Data<SharedTaxonomy>,
+    config: web::Data<Config>,
+    mut payload: Multipart,
+) -> Result<impl Responder, actix_web::Error> {
+    let (file_name, bytes) = read_upload_file(&mut payload, config.upload_limit_bytes).await?;
+    let request: PredictRequest = serde_json::from_slice(&bytes)
+        .map_err(|e| ErrorBadRequest(format!("invalid JSON file: {}", e)))?;
+
+    // Даты сравниваются в хранилище как строки, поэтому допускаем только YYYY-MM-DD
+    if let Some(bad) = request.data.iter().find(|s| {
+        s.date.as_deref().is_some_and(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").is_err())
+    }) {
+        return Err(ErrorBadRequest(format!("sample {}: date must be in YYYY-MM-DD format", bad.id)));
+    }
+
+    let PredictOutput { items, errors } = predictor.predict(&request.data).await?;
+    metrics::record_labels(&items);
+    let failed: HashSet<i64> = errors.iter().map(|(id, _)| *id).collect();
+    let mut by_id: HashMap<i64, PredictItem> = items.into_iter().map(|p| (p.id, p)).collect();
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+
+    let mut reviews = Vec::with_capacity(request.data.len());
+    let mut enriched = Vec::with_capacity(request.data.len());
+    for sample in request.data {
+        let labels = by_id.remove(&sample.id).map(|p| p.topics).unwrap_or_default();
+
+        // Отзывы без предсказания не попадают в статистику, но остаются в выгрузке
+        if !failed.contains(&sample.id) {
+            reviews.push(NewReview {
+                id: sample.id,
+                date: sample.date.clone().unwrap_or_else(|| today.clone()),
+                region: sample.region.clone().unwrap_or_default(),
+                text: sample.text.clone(),
+                topics: labels.iter().map(|t| (t.topic.clone(), t.sentiment)).collect(),
+            });
+        }
+        let (topics, sentiments) = labels.into_iter().map(|t| (t.topic, t.sentiment.as_label().to_string())).unzip();
+        enriched.push(EnrichedSample { sample, topics, sentiments });
+    }
+
+    let count = enriched.len();
+    let payload = serde_json::to_string(&EnrichedDataset { data: enriched }).map_err(ErrorInternalServerError)?;
+    let name = file_name.clone();
+    // Метки, которых нет в таксономии, сохраняются как новые топики
+    let upload_id = change_taxonomy(store, taxonomy, move |s| s.save_upload(&name, &reviews, &payload)).await?;
+    info!("Upload {} ({:?}) processed: {} reviews, {} failed", upload_id, file_name, count, errors.len());
+
+    let errors = errors.iter().map(|(id, e)| e.to_item(Some(*id))).collect();
+    Ok(web::Json(UploadResponse { upload_id, file_name, count, errors }))
 }
 
+#[get("/download")]
+async fn get_download(store: web::Data<ReviewStore>, query: web::Query<DownloadQuery>) -> Result<HttpResponse, actix_web::Error> {
+    let upload_id = query.upload_id;
+    let (_, payload) = with_store(store, move |s| s.upload_payload(upload_id))
+        .await?
+        .ok_or_else(|| ErrorNotFound("no uploaded file found"))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json; charset=utf-8")
+        .insert_header(ContentDisposition::attachment("predictions.json"))
+        .body(payload))
+}
+
+#[post("/jobs")]
+async fn post_job(
+    models: web::Data<ReloadablePredictor>,
+    jobs: web::Data<JobManager>,
+    query: web::Query<PredictQuery>,
+    payload: web::Json<PredictRequest>,
+) -> HttpResponse {
+    // Вся задача выполняется на модели, активной в момент постановки
+    let Some(predictor) = models.get(query.model.as_deref()) else {
+        return unknown_model(query.model.as_deref());
+    };
+    let job = jobs.submit(predictor, payload.into_inner().data, query.top_k.unwrap_or(0));
+    HttpResponse::Accepted().json(job.snapshot())
+}
+
+#[get("/jobs/{job_id}")]
+async fn get_job(jobs: web::Data<JobManager>, path: web::Path<Uuid>) -> Result<impl Responder, actix_web::Error> {
+    let job_id = path.into_inner();
+    let job = jobs.get(&job_id).ok_or_else(|| ErrorNotFound(format!("job {} not found", job_id)))?;
+    Ok(web::Json(job.snapshot()))
+}
+
+/// Отдаёт предсказания потоком, не собирая весь JSON в памяти
+#[get("/jobs/{job_id}/result")]
+async fn get_job_result(
+    jobs: web::Data<JobManager>,
+    config: web::Data<Config>,
+    path: web::Path<Uuid>,
+    query: web::Query<FormatQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    const ITEMS_PER_CHUNK: usize = 256;
+
+    let job_id = path.into_inner();
+    let job = jobs.get(&job_id).ok_or_else(|| ErrorNotFound(format!("job {} not found", job_id)))?;
+    let status = job.status();
+    let results = job
+        .results()
+        .ok_or_else(|| ErrorConflict(format!("job {} is not finished yet", job_id)))?;
+
+    let header = format!(
+        r#"{{"job_id":"{}","status":{},"predictions":["#,
+        job_id,
+        serde_json::to_string(&status).map_err(ErrorInternalServerError)?
+    );
+    let format = response_format(query.format, &config);
+    let chunks = (0..results.items.len()).step_by(ITEMS_PER_CHUNK);
+    let tail_results = results.clone();
+    let body = stream::once(async move { Ok::<_, serde_json::Error>(Bytes::from(header)) })
+        .chain(stream::iter(chunks).map(move |start| {
+            let end = (start + ITEMS_PER_CHUNK).min(results.items.len());
+            let mut buf = Vec::new();
+            for (i, item) in results.items[start..end].iter().enumerate() {
+                if start + i > 0 {
+                    buf.push(b',');
+                }
+                match format {
+                    ResponseFormat::Structured => serde_json::to_writer(&mut buf, item)?,
+                    ResponseFormat::Legacy => serde_json::to_writer(&mut buf, &LegacyPredictItem::from(item.clone()))?,
+                }
+            }
+            Ok(Bytes::from(buf))
+        }))
+        .chain(stream::once(async move {
+            let mut buf = b"],\"errors\":".to_vec();
+            serde_json::to_writer(&mut buf, &tail_results.error_items())?;
+            buf.push(b'}');
+            Ok(Bytes::from(buf))
+        }));
+
+    Ok(HttpResponse::Ok().content_type("application/json").streaming(body))
+}
+
+#[delete("/jobs/{job_id}")]
+async fn delete_job(jobs: web::Data<JobManager>, path: web::Path<Uuid>) -> Result<impl Responder, actix_web::Error> {
+    let job_id = path.into_inner();
+    let job = jobs.cancel(&job_id).ok_or_else(|| ErrorNotFound(format!("job {} not found", job_id)))?;
+    Ok(HttpResponse::Accepted().json(job.snapshot()))
+}
+
+/// Liveness: процесс жив и обслуживает HTTP
+#[get("/healthz")]
+async fn healthz() -> impl Responder {
+    web::Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Readiness: модель загружена, внешний сервис доступен, база открыта
+#[get("/readyz")]
+async fn readyz(predictor: web::Data<dyn Predictor>, store: web::Data<ReviewStore>) -> HttpResponse {
+    let check = |result: Result<(), String>| match result {
+        Ok(()) => ReadinessCheck { ok: true, error: None },
+        Err(error) => ReadinessCheck { ok: false, error: Some(error) },
+    };
+
+    let predictor_check = check(predictor.check_ready().await.map_err(|e| e.to_string()));
+    let store_check = check(match web::block(move || store.ping()).await {
+        Ok(result) => result.map_err(|e| format!("{:#}", e)),
+        Err(e) => Err(e.to_string()),
+    });
+
+    let ready = predictor_check.ok && store_check.ok;
+    let checks = [("predictor", predictor_check), ("database", store_check)].into_iter().collect();
+    let body = ReadinessResponse { status: if ready { "ready" } else { "not_ready" }, checks };
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+#[get("/model/info")]
+async fn get_model_info(predictor: web::Data<dyn Predictor>) -> impl Responder {
+    web::Json(predictor.describe())
+}
+
+/// Загруженные версии модели
+#[get("/models")]
+async fn get_models(models: web::Data<ReloadablePredictor>) -> impl Responder {
+    web::Json(models.registry().list())
+}
+
+/// Согласованность теневых моделей с версией по умолчанию
+#[get("/shadow/stats")]
+async fn get_shadow_stats(models: web::Data<ReloadablePredictor>) -> impl Responder {
+    let registry = models.registry();
+    web::Json(ShadowStatsResponse {
+        primary: registry.default_version().to_string(),
+        shadows: registry.shadow_stats().map(|stats| stats.snapshot()).unwrap_or_default(),
+    })
+}
+
+/// Последние отзывы, на которых теневые модели разошлись с основной
+#[get("/shadow/disagreements")]
+async fn get_shadow_disagreements(
+    models: web::Data<ReloadablePredictor>,
+    query: web::Query<ShadowDisagreementsQuery>,
+) -> impl Responder {
+    let limit = query.limit.unwrap_or(50).min(500);
+    let disagreements = models
+        .registry()
+        .shadow_stats()
+        .map(|stats| stats.disagreements(query.shadow.as_deref(), limit))
+        .unwrap_or_default();
+    web::Json(disagreements)
+}
+
+#[get("/metrics")]
+async fn get_metrics() -> Result<HttpResponse, actix_web::Error> {
+    let body = metrics::render().map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().content_type("text/plain; version=0.0.4").body(body))
+}
+
+/// Загружает модель из `MODEL_DIR` заново и подменяет активный предиктор после самопроверки
+#[post("/admin/reload")]
+async fn post_admin_reload(
+    req: actix_web::HttpRequest,
+    config: web::Data<Config>,
+    reloader: web::Data<ModelReloader>,
+) -> Result<HttpResponse, actix_web::Error> {
+    authorize_admin(&req, &config)?;
+
+    match reloader.reload().await {
+        Ok(models) => Ok(HttpResponse::Ok().json(models)),
+        Err(e) => {
+            error!("Model reload failed, keeping current predictor: {:#}", e);
+            Ok(HttpResponse::UnprocessableEntity().json(serde_json::json!({ "error": format!("{:#}", e) })))
+        }
+    }
+}
+
+/// Активный набор правил ключевых слов
+#[get("/rules")]
+async fn get_rules(rules: web::Data<SharedRules>) -> impl Responder {
+    web::Json(rules.current().info().clone())
+}
+
+/// Какие правила сработали на тексте и что они дали
+#[post("/rules/explain")]
+async fn post_rules_explain(rules: web::Data<SharedRules>, body: web::Json<RulesExplainRequest>) -> impl Responder {
+    web::Json(rules.current().evaluate(&body.text))
+}
+
+/// Перечитывает файл правил `rules_path`; при ошибке остаются прежние правила
+#[post("/admin/rules/reload")]
+async fn post_admin_rules_reload(
+    req: actix_web::HttpRequest,
+    config: web::Data<Config>,
+    rules: web::Data<SharedRules>,
+) -> Result<HttpResponse, actix_web::Error> {
+    authorize_admin(&req, &config)?;
+
+    match web::block(move || rules.reload()).await.map_err(ErrorInternalServerError)? {
+        Ok(info) => Ok(HttpResponse::Ok().json(info)),
+        Err(e) => {
+            error!("Keyword rules reload failed, keeping current rules: {:#}", e);
+            Ok(HttpResponse::UnprocessableEntity().json(serde_json::json!({ "error": format!("{:#}", e) })))
+        }
+    }
+}
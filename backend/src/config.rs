@@ -1,36 +1,493 @@
-use std::path::PathBuf;
-use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone)]
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, ValueEnum};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+/// Файл конфигурации, который читается из рабочего каталога, если `--config` не указан
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Какой предиктор использовать; `auto` — цепочка Proxy -> ONNX -> Native -> Mock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PredictorKind {
+    #[default]
+    Auto,
+    Proxy,
+    Onnx,
+    Native,
+    Mock,
+}
+
+/// Как `EnsemblePredictor` объединяет ответы участников
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum EnsembleStrategy {
+    /// Все топики участников, тональность — взвешенным большинством
+    #[default]
+    Union,
+    /// Только топики, найденные всеми ответившими участниками
+    Intersection,
+    /// Топики, у которых взвешенная по участникам вероятность не ниже `ensemble_vote_threshold`
+    Weighted,
+    /// Первый участник (правила) задаёт свои топики и их тональность, остальные дополняют
+    RulesOverride,
+}
+
+#[derive(Clone)]
 pub struct Config {
     pub server_host: String,
     pub server_port: u16,
+    pub predictor: PredictorKind,
+    /// Не запускаться, если выбранный предиктор не загрузился или не прошёл самопроверку
+    pub predictor_strict: bool,
+    pub startup_self_test: bool,
+    /// Период проверки `model_dir` на новые файлы модели; 0 — без слежения
+    pub model_watch_interval_secs: u64,
+    /// Файл правил ключевых слов (YAML или JSON); без него — встроенные правила
+    pub rules_path: Option<PathBuf>,
+    /// Период проверки `rules_path` на изменения; 0 — без слежения
+    pub rules_watch_interval_secs: u64,
+    /// Токен для `/admin/*` и изменения `/topics` (`Authorization: Bearer ...`); без него эндпоинты открыты
+    pub admin_token: Option<String>,
     pub model_dir: PathBuf,
+    /// Версия модели по умолчанию (`v43`); без неё — последняя найденная в `model_dir`
+    pub default_model: Option<String>,
+    /// Версии из реестра (или `mock`), которые сравниваются с версией по умолчанию на живом трафике
+    pub shadow_models: Vec<String>,
+    /// Максимум одновременных фоновых теневых вызовов
+    pub shadow_max_in_flight: usize,
+    /// Участники версии `ensemble`: `v43`, `mock` или `v43:2.0` с весом
+    pub ensemble_members: Vec<String>,
+    pub ensemble_strategy: EnsembleStrategy,
+    pub ensemble_vote_threshold: f32,
+    /// Ответ `/predict` в прежнем виде (параллельные `topics`/`sentiments`) для клиентов serve.py
+    pub legacy_predict_response: bool,
     pub proxy_url: Option<String>,
+    pub proxy_timeout_ms: u64,
+    pub proxy_connect_timeout_ms: u64,
+    pub proxy_retries: u32,
+    pub proxy_backoff_ms: u64,
+    pub breaker_threshold: u32,
+    pub breaker_cooldown_secs: u64,
+    pub proxy_chunk_size: usize,
+    pub proxy_concurrency: usize,
     pub static_dir: PathBuf,
+    pub onnx_batch_size: usize,
+    pub topic_threshold: f32,
+    pub tokenizer_lowercase: bool,
+    pub db_path: PathBuf,
+    pub upload_limit_bytes: usize,
+    pub job_workers: usize,
+    pub job_chunk_size: usize,
+    pub job_ttl_secs: u64,
+    /// Пустой список или `*` — разрешены любые источники
+    pub cors_origins: Vec<String>,
 }
 
-impl Config {
-    pub fn from_env() -> Self {
-        let server_host = env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
-        let server_port = env::var("SERVER_PORT")
-            .unwrap_or_else(|_| "8080".to_string())
-            .parse()
-            .unwrap_or(8080);
-        
-        let model_dir_path = env::var("MODEL_DIR").unwrap_or_else(|_| "ai_model".to_string());
-        let model_dir = std::env::current_dir().unwrap().join(model_dir_path);
-        
-        let proxy_url = env::var("PREDICT_URL").ok().filter(|s| !s.trim().is_empty());
-        
-        let static_dir = std::env::current_dir().unwrap().join("frontend");
-
-        Self {
+/// Конфигурация попадает в лог при старте, поэтому токен скрыт
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
             server_host,
             server_port,
+            predictor,
+            predictor_strict,
+            startup_self_test,
+            model_watch_interval_secs,
+            rules_path,
+            rules_watch_interval_secs,
+            admin_token,
             model_dir,
+            default_model,
+            shadow_models,
+            shadow_max_in_flight,
+            ensemble_members,
+            ensemble_strategy,
+            ensemble_vote_threshold,
+            legacy_predict_response,
             proxy_url,
+            proxy_timeout_ms,
+            proxy_connect_timeout_ms,
+            proxy_retries,
+            proxy_backoff_ms,
+            breaker_threshold,
+            breaker_cooldown_secs,
+            proxy_chunk_size,
+            proxy_concurrency,
             static_dir,
+            onnx_batch_size,
+            topic_threshold,
+            tokenizer_lowercase,
+            db_path,
+            upload_limit_bytes,
+            job_workers,
+            job_chunk_size,
+            job_ttl_secs,
+            cors_origins,
+        } = self;
+        f.debug_struct("Config")
+            .field("server_host", server_host)
+            .field("server_port", server_port)
+            .field("predictor", predictor)
+            .field("predictor_strict", predictor_strict)
+            .field("startup_self_test", startup_self_test)
+            .field("model_watch_interval_secs", model_watch_interval_secs)
+            .field("rules_path", rules_path)
+            .field("rules_watch_interval_secs", rules_watch_interval_secs)
+            .field("admin_token", &admin_token.as_ref().map(|_| "***"))
+            .field("model_dir", model_dir)
+            .field("default_model", default_model)
+            .field("shadow_models", shadow_models)
+            .field("shadow_max_in_flight", shadow_max_in_flight)
+            .field("ensemble_members", ensemble_members)
+            .field("ensemble_strategy", ensemble_strategy)
+            .field("ensemble_vote_threshold", ensemble_vote_threshold)
+            .field("legacy_predict_response", legacy_predict_response)
+            .field("proxy_url", proxy_url)
+            .field("proxy_timeout_ms", proxy_timeout_ms)
+            .field("proxy_connect_timeout_ms", proxy_connect_timeout_ms)
+            .field("proxy_retries", proxy_retries)
+            .field("proxy_backoff_ms", proxy_backoff_ms)
+            .field("breaker_threshold", breaker_threshold)
+            .field("breaker_cooldown_secs", breaker_cooldown_secs)
+            .field("proxy_chunk_size", proxy_chunk_size)
+            .field("proxy_concurrency", proxy_concurrency)
+            .field("static_dir", static_dir)
+            .field("onnx_batch_size", onnx_batch_size)
+            .field("topic_threshold", topic_threshold)
+            .field("tokenizer_lowercase", tokenizer_lowercase)
+            .field("db_path", db_path)
+            .field("upload_limit_bytes", upload_limit_bytes)
+            .field("job_workers", job_workers)
+            .field("job_chunk_size", job_chunk_size)
+            .field("job_ttl_secs", job_ttl_secs)
+            .field("cors_origins", cors_origins)
+            .finish()
+    }
+}
+
+#[derive(Debug, Parser)]
+#[command(name = "backend", about = "Kabanchiki backend server")]
+struct Cli {
+    /// Путь к TOML-файлу конфигурации (по умолчанию `config.toml`, если он есть)
+    #[arg(long, env = "CONFIG_FILE")]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    layer: ConfigLayer,
+}
+
+/// Один слой конфигурации; ключи TOML совпадают с именами полей,
+/// у каждого поля есть переменная окружения и флаг командной строки
+#[derive(Debug, Default, Deserialize, Args)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigLayer {
+    #[arg(long, env = "SERVER_HOST")]
+    server_host: Option<String>,
+    #[arg(long, env = "SERVER_PORT")]
+    server_port: Option<u16>,
+    #[arg(long, env = "PREDICTOR")]
+    predictor: Option<PredictorKind>,
+    #[arg(long, env = "PREDICTOR_STRICT", value_parser = clap::builder::BoolishValueParser::new())]
+    predictor_strict: Option<bool>,
+    #[arg(long, env = "STARTUP_SELF_TEST", value_parser = clap::builder::BoolishValueParser::new())]
+    startup_self_test: Option<bool>,
+    #[arg(long, env = "MODEL_DIR")]
+    model_dir: Option<PathBuf>,
+    #[arg(long, env = "DEFAULT_MODEL")]
+    default_model: Option<String>,
+    #[arg(long = "shadow-model", env = "SHADOW_MODELS", value_delimiter = ',')]
+    shadow_models: Option<Vec<String>>,
+    #[arg(long, env = "SHADOW_MAX_IN_FLIGHT")]
+    shadow_max_in_flight: Option<usize>,
+    #[arg(long = "ensemble-member", env = "ENSEMBLE_MEMBERS", value_delimiter = ',')]
+    ensemble_members: Option<Vec<String>>,
+    #[arg(long, env = "ENSEMBLE_STRATEGY")]
+    ensemble_strategy: Option<EnsembleStrategy>,
+    #[arg(long, env = "ENSEMBLE_VOTE_THRESHOLD")]
+    ensemble_vote_threshold: Option<f32>,
+    #[arg(long, env = "LEGACY_PREDICT_RESPONSE", value_parser = clap::builder::BoolishValueParser::new())]
+    legacy_predict_response: Option<bool>,
+    #[arg(long, env = "MODEL_WATCH_INTERVAL_SECS")]
+    model_watch_interval_secs: Option<u64>,
+    #[arg(long, env = "RULES_PATH")]
+    rules_path: Option<PathBuf>,
+    #[arg(long, env = "RULES_WATCH_INTERVAL_SECS")]
+    rules_watch_interval_secs: Option<u64>,
+    #[arg(long, env = "ADMIN_TOKEN")]
+    admin_token: Option<String>,
+    #[arg(long, env = "PREDICT_URL")]
+    predict_url: Option<String>,
+    #[arg(long, env = "PREDICT_TIMEOUT_MS")]
+    predict_timeout_ms: Option<u64>,
+    #[arg(long, env = "PREDICT_CONNECT_TIMEOUT_MS")]
+    predict_connect_timeout_ms: Option<u64>,
+    #[arg(long, env = "PREDICT_RETRIES")]
+    predict_retries: Option<u32>,
+    #[arg(long, env = "PREDICT_RETRY_BACKOFF_MS")]
+    predict_retry_backoff_ms: Option<u64>,
+    #[arg(long, env = "PREDICT_BREAKER_THRESHOLD")]
+    predict_breaker_threshold: Option<u32>,
+    #[arg(long, env = "PREDICT_BREAKER_COOLDOWN_SECS")]
+    predict_breaker_cooldown_secs: Option<u64>,
+    #[arg(long, env = "PREDICT_CHUNK_SIZE")]
+    predict_chunk_size: Option<usize>,
+    #[arg(long, env = "PREDICT_CONCURRENCY")]
+    predict_concurrency: Option<usize>,
+    #[arg(long, env = "STATIC_DIR")]
+    static_dir: Option<PathBuf>,
+    #[arg(long, env = "ONNX_BATCH_SIZE")]
+    onnx_batch_size: Option<usize>,
+    #[arg(long, env = "TOPIC_THRESHOLD")]
+    topic_threshold: Option<f32>,
+    /// Используется только для vocab.txt; tokenizer.json несёт собственные настройки нормализации
+    #[arg(long, env = "TOKENIZER_LOWERCASE", value_parser = clap::builder::BoolishValueParser::new())]
+    tokenizer_lowercase: Option<bool>,
+    #[arg(long, env = "DB_PATH")]
+    db_path: Option<PathBuf>,
+    #[arg(long, env = "UPLOAD_LIMIT_MB")]
+    upload_limit_mb: Option<usize>,
+    #[arg(long, env = "JOB_WORKERS")]
+    job_workers: Option<usize>,
+    #[arg(long, env = "JOB_CHUNK_SIZE")]
+    job_chunk_size: Option<usize>,
+    #[arg(long, env = "JOB_TTL_SECS")]
+    job_ttl_secs: Option<u64>,
+    #[arg(long = "cors-origin", env = "CORS_ORIGINS", value_delimiter = ',')]
+    cors_origins: Option<Vec<String>>,
+}
+
+impl ConfigLayer {
+    /// Значения `upper` перекрывают значения `self`
+    fn overlay(self, upper: ConfigLayer) -> ConfigLayer {
+        ConfigLayer {
+            server_host: upper.server_host.or(self.server_host),
+            server_port: upper.server_port.or(self.server_port),
+            predictor: upper.predictor.or(self.predictor),
+            predictor_strict: upper.predictor_strict.or(self.predictor_strict),
+            startup_self_test: upper.startup_self_test.or(self.startup_self_test),
+            model_dir: upper.model_dir.or(self.model_dir),
+            default_model: upper.default_model.or(self.default_model),
+            shadow_models: upper.shadow_models.or(self.shadow_models),
+            shadow_max_in_flight: upper.shadow_max_in_flight.or(self.shadow_max_in_flight),
+            ensemble_members: upper.ensemble_members.or(self.ensemble_members),
+            ensemble_strategy: upper.ensemble_strategy.or(self.ensemble_strategy),
+            ensemble_vote_threshold: upper.ensemble_vote_threshold.or(self.ensemble_vote_threshold),
+            legacy_predict_response: upper.legacy_predict_response.or(self.legacy_predict_response),
+            model_watch_interval_secs: upper.model_watch_interval_secs.or(self.model_watch_interval_secs),
+            rules_path: upper.rules_path.or(self.rules_path),
+            rules_watch_interval_secs: upper.rules_watch_interval_secs.or(self.rules_watch_interval_secs),
+            admin_token: upper.admin_token.or(self.admin_token),
+            predict_url: upper.predict_url.or(self.predict_url),
+            predict_timeout_ms: upper.predict_timeout_ms.or(self.predict_timeout_ms),
+            predict_connect_timeout_ms: upper.predict_connect_timeout_ms.or(self.predict_connect_timeout_ms),
+            predict_retries: upper.predict_retries.or(self.predict_retries),
+            predict_retry_backoff_ms: upper.predict_retry_backoff_ms.or(self.predict_retry_backoff_ms),
+            predict_breaker_threshold: upper.predict_breaker_threshold.or(self.predict_breaker_threshold),
+            predict_breaker_cooldown_secs: upper.predict_breaker_cooldown_secs.or(self.predict_breaker_cooldown_secs),
+            predict_chunk_size: upper.predict_chunk_size.or(self.predict_chunk_size),
+            predict_concurrency: upper.predict_concurrency.or(self.predict_concurrency),
+            static_dir: upper.static_dir.or(self.static_dir),
+            onnx_batch_size: upper.onnx_batch_size.or(self.onnx_batch_size),
+            topic_threshold: upper.topic_threshold.or(self.topic_threshold),
+            tokenizer_lowercase: upper.tokenizer_lowercase.or(self.tokenizer_lowercase),
+            db_path: upper.db_path.or(self.db_path),
+            upload_limit_mb: upper.upload_limit_mb.or(self.upload_limit_mb),
+            job_workers: upper.job_workers.or(self.job_workers),
+            job_chunk_size: upper.job_chunk_size.or(self.job_chunk_size),
+            job_ttl_secs: upper.job_ttl_secs.or(self.job_ttl_secs),
+            cors_origins: upper.cors_origins.or(self.cors_origins),
         }
     }
 }
+
+impl Config {
+    /// Собирает конфигурацию по слоям: значения по умолчанию, TOML-файл,
+    /// переменные окружения, аргументы командной строки (каждый следующий перекрывает предыдущий)
+    pub fn load() -> Result<Self> {
+        let cli = Cli::parse();
+
+        let file_layer = match &cli.config {
+            Some(path) => read_config_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => read_config_file(Path::new(DEFAULT_CONFIG_FILE))?,
+            None => ConfigLayer::default(),
+        };
+
+        let config = Self::from_layer(file_layer.overlay(cli.layer))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_layer(layer: ConfigLayer) -> Result<Self> {
+        let path = |value: Option<PathBuf>, default: &str| -> Result<PathBuf> {
+            let value = value.unwrap_or_else(|| PathBuf::from(default));
+            std::path::absolute(&value).with_context(|| format!("failed to resolve path {:?}", value))
+        };
+
+        Ok(Self {
+            server_host: layer.server_host.unwrap_or_else(|| "0.0.0.0".to_string()),
+            server_port: layer.server_port.unwrap_or(8080),
+            predictor: layer.predictor.unwrap_or_default(),
+            predictor_strict: layer.predictor_strict.unwrap_or(false),
+            startup_self_test: layer.startup_self_test.unwrap_or(true),
+            model_dir: path(layer.model_dir, "ai_model")?,
+            default_model: layer.default_model.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+            shadow_models: layer
+                .shadow_models
+                .unwrap_or_default()
+                .into_iter()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            shadow_max_in_flight: layer.shadow_max_in_flight.unwrap_or(4),
+            ensemble_members: layer
+                .ensemble_members
+                .unwrap_or_default()
+                .into_iter()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            ensemble_strategy: layer.ensemble_strategy.unwrap_or_default(),
+            ensemble_vote_threshold: layer.ensemble_vote_threshold.unwrap_or(0.5),
+            legacy_predict_response: layer.legacy_predict_response.unwrap_or(false),
+            model_watch_interval_secs: layer.model_watch_interval_secs.unwrap_or(0),
+            rules_path: layer.rules_path.map(|p| path(Some(p), "")).transpose()?,
+            rules_watch_interval_secs: layer.rules_watch_interval_secs.unwrap_or(0),
+            admin_token: layer.admin_token.filter(|t| !t.is_empty()),
+            proxy_url: layer.predict_url.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+            proxy_timeout_ms: layer.predict_timeout_ms.unwrap_or(10_000),
+            proxy_connect_timeout_ms: layer.predict_connect_timeout_ms.unwrap_or(2_000),
+            proxy_retries: layer.predict_retries.unwrap_or(2),
+            proxy_backoff_ms: layer.predict_retry_backoff_ms.unwrap_or(200),
+            breaker_threshold: layer.predict_breaker_threshold.unwrap_or(5),
+            breaker_cooldown_secs: layer.predict_breaker_cooldown_secs.unwrap_or(30),
+            proxy_chunk_size: layer.predict_chunk_size.unwrap_or(256),
+            proxy_concurrency: layer.predict_concurrency.unwrap_or(4),
+            static_dir: path(layer.static_dir, "frontend")?,
+            onnx_batch_size: layer.onnx_batch_size.unwrap_or(32),
+            topic_threshold: layer.topic_threshold.unwrap_or(0.5),
+            tokenizer_lowercase: layer.tokenizer_lowercase.unwrap_or(true),
+            db_path: path(layer.db_path, "data/kabanchiki.db")?,
+            upload_limit_bytes: layer.upload_limit_mb.unwrap_or(50).saturating_mul(1024 * 1024),
+            job_workers: layer.job_workers.unwrap_or(2),
+            job_chunk_size: layer.job_chunk_size.unwrap_or(64),
+            job_ttl_secs: layer.job_ttl_secs.unwrap_or(3600),
+            cors_origins: layer
+                .cors_origins
+                .unwrap_or_default()
+                .into_iter()
+                .map(|o| o.trim().trim_end_matches('/').to_string())
+                .filter(|o| !o.is_empty())
+                .collect(),
+        })
+    }
+
+    /// Проверяет значения целиком и сообщает обо всех ошибках сразу
+    fn validate(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        if self.server_port == 0 {
+            errors.push("server_port must be in 1..=65535".to_string());
+        }
+        for (name, value) in [
+            ("onnx_batch_size", self.onnx_batch_size),
+            ("job_workers", self.job_workers),
+            ("job_chunk_size", self.job_chunk_size),
+            ("predict_chunk_size", self.proxy_chunk_size),
+            ("predict_concurrency", self.proxy_concurrency),
+            ("shadow_max_in_flight", self.shadow_max_in_flight),
+            ("predict_breaker_threshold", self.breaker_threshold as usize),
+            ("upload_limit_mb", self.upload_limit_bytes),
+        ] {
+            if value == 0 {
+                errors.push(format!("{} must be greater than 0", name));
+            }
+        }
+        if self.proxy_timeout_ms == 0 || self.proxy_connect_timeout_ms == 0 {
+            errors.push("predict_timeout_ms and predict_connect_timeout_ms must be greater than 0".to_string());
+        }
+        if !(self.topic_threshold > 0.0 && self.topic_threshold < 1.0) {
+            errors.push(format!("topic_threshold must be in (0, 1), got {}", self.topic_threshold));
+        }
+        if !(self.ensemble_vote_threshold > 0.0 && self.ensemble_vote_threshold <= 1.0) {
+            errors.push(format!("ensemble_vote_threshold must be in (0, 1], got {}", self.ensemble_vote_threshold));
+        }
+        if let Err(e) = self.ensemble_weights() {
+            errors.push(e.to_string());
+        }
+
+        match &self.proxy_url {
+            Some(url) if !reqwest::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https")) => {
+                errors.push(format!("predict_url {:?} is not a valid http(s) URL", url));
+            }
+            None if self.predictor == PredictorKind::Proxy => {
+                errors.push("predictor = proxy requires predict_url".to_string());
+            }
+            _ => {}
+        }
+
+        for origin in self.cors_origins.iter().filter(|o| o.as_str() != "*") {
+            let valid = reqwest::Url::parse(origin)
+                .is_ok_and(|u| matches!(u.scheme(), "http" | "https") && u.host().is_some() && u.path() == "/");
+            if !valid {
+                errors.push(format!("cors origin {:?} must look like http(s)://host[:port]", origin));
+            }
+        }
+
+        if !self.static_dir.is_dir() {
+            errors.push(format!("static_dir {:?} does not exist", self.static_dir));
+        }
+        if !self.model_dir.is_dir() {
+            warn!("Model directory {:?} does not exist", self.model_dir);
+        }
+
+        if !errors.is_empty() {
+            bail!("invalid configuration:\n  - {}", errors.join("\n  - "));
+        }
+        Ok(())
+    }
+
+    /// Участники ансамбля с весами (по умолчанию 1.0)
+    pub fn ensemble_weights(&self) -> Result<Vec<(String, f32)>> {
+        self.ensemble_members
+            .iter()
+            .map(|spec| match spec.split_once(':') {
+                None => Ok((spec.clone(), 1.0)),
+                Some((name, weight)) => match weight.trim().parse::<f32>() {
+                    Ok(w) if w > 0.0 && w.is_finite() => Ok((name.trim().to_string(), w)),
+                    _ => bail!("ensemble member {:?} must look like name or name:weight with weight > 0", spec),
+                },
+            })
+            .collect()
+    }
+
+    /// `true`, если CORS не ограничен конкретными источниками
+    pub fn cors_permissive(&self) -> bool {
+        self.cors_origins.is_empty() || self.cors_origins.iter().any(|o| o == "*")
+    }
+}
+
+fn read_config_file(path: &Path) -> Result<ConfigLayer> {
+    let content = fs::read_to_string(path).with_context(|| format!("failed to read config file {:?}", path))?;
+    let layer = toml::from_str(&content).with_context(|| format!("invalid config file {:?}", path))?;
+    info!("Loaded config file {:?}", path);
+    Ok(layer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_hides_admin_token() {
+        let layer = ConfigLayer { admin_token: Some("s3cret-token".to_string()), ..ConfigLayer::default() };
+        let config = Config::from_layer(layer).expect("defaults are valid");
+        let printed = format!("{:?}", config);
+        assert!(!printed.contains("s3cret-token"));
+        assert!(printed.contains(r#"admin_token: Some("***")"#));
+    }
+}
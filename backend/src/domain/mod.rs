@@ -1,13 +1,50 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+/// Топик таксономии: предикторы могут называть его по имени или по любому из синонимов
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Topic { pub id: i32, pub name: String }
+pub struct Topic {
+    pub id: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub synonyms: Vec<String>,
+    /// Неактивный топик не попадает в предсказания, но его история сохраняется
+    pub active: bool,
+}
+
+/// Тело `POST /topics` и `PUT /topics/{id}`
+#[derive(Debug, Deserialize, Clone)]
+pub struct TopicInput {
+    pub name: String,
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+    #[serde(default)]
+    pub synonyms: Vec<String>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopicsQuery { pub include_inactive: Option<bool> }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SentimentStats { pub positive: i64, pub neutral: i64, pub negative: i64 }
 
+/// Статистика топика; при `rollup=true` включает отзывы дочерних топиков, которые
+/// перечислены в `children` (глубже `depth` — только в сумме родителя)
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct TopicsStatsItem { pub id: i32, pub name: String, pub stats: SentimentStats }
+pub struct TopicsStatsItem {
+    pub id: i32,
+    pub name: String,
+    pub stats: SentimentStats,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TopicsStatsItem>,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Period { pub from: String, pub to: String }
@@ -21,13 +58,51 @@ pub struct TimelinePoint { pub date: String, pub positive: i64, pub neutral: i64
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TimelineResponse { pub topic: Topic, pub timeline: Vec<TimelinePoint> }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
-#[allow(dead_code)]
 pub enum Sentiment { Positive, Neutral, Negative }
 
+impl Sentiment {
+    /// Разбирает метку как английский код (`positive`) или ответ предиктора (`положительно`)
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_lowercase().as_str() {
+            "positive" | "положительно" => Some(Self::Positive),
+            "neutral" | "нейтрально" => Some(Self::Neutral),
+            "negative" | "отрицательно" => Some(Self::Negative),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Positive => "positive",
+            Self::Neutral => "neutral",
+            Self::Negative => "negative",
+        }
+    }
+
+    /// Метка в формате serve.py (`положительно`)
+    pub fn as_label(&self) -> &'static str {
+        match self {
+            Self::Positive => "положительно",
+            Self::Neutral => "нейтрально",
+            Self::Negative => "отрицательно",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-pub struct ReviewItem { pub id: i64, pub date: String, pub sentiment: String, pub text: String, pub region: String }
+pub struct ReviewItem {
+    pub id: i64,
+    pub date: String,
+    pub sentiment: String,
+    pub text: String,
+    pub region: String,
+    /// Id отзыва в загруженном файле
+    pub external_id: i64,
+    /// Загрузка, из которой пришёл отзыв; нет у отзывов, сохранённых до схемы 3
+    pub upload_id: Option<i64>,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ReviewsFilters { pub topic_id: Option<i32>, pub sentiment: Option<String>, pub period: Option<Period> }
@@ -39,10 +114,24 @@ pub struct Pagination { pub page: i64, pub limit: i64, pub total: i64 }
 pub struct ReviewsResponse { pub filters: ReviewsFilters, pub pagination: Pagination, pub reviews: Vec<ReviewItem> }
 
 #[derive(Debug, Deserialize)]
-pub struct StatsQuery { pub date_from: String, pub date_to: String, pub _region: Option<String> }
+pub struct StatsQuery {
+    pub date_from: String,
+    pub date_to: String,
+    pub region: Option<String>,
+    pub rollup: Option<bool>,
+    /// Глубина дерева при `rollup=true`: 0 — только корневые топики
+    pub depth: Option<usize>,
+}
 
 #[derive(Debug, Deserialize)]
-pub struct TimelineQuery { pub _date_from: String, pub _date_to: String, pub group_by: String, pub _region: Option<String> }
+pub struct TimelineQuery {
+    pub date_from: String,
+    pub date_to: String,
+    pub group_by: String,
+    pub region: Option<String>,
+    /// Учитывать отзывы всех дочерних топиков
+    pub rollup: Option<bool>,
+}
 
 #[derive(Debug, Deserialize)]
 pub struct ReviewsQuery {
@@ -50,7 +139,7 @@ pub struct ReviewsQuery {
     pub date_from: Option<String>,
     pub date_to: Option<String>,
     pub sentiment: Option<String>,
-    pub _region: Option<String>,
+    pub region: Option<String>,
     pub page: Option<i64>,
     pub limit: Option<i64>,
 }
@@ -58,13 +147,298 @@ pub struct ReviewsQuery {
 #[derive(Debug, Deserialize)]
 pub struct PredictRequest { pub data: Vec<PredictSample> }
 
+/// `?model=v43` — версия модели из реестра (по умолчанию `default_model`);
+/// `?top_k=3` — добавить в ответ столько отклонённых топиков; `?format=legacy` — прежний формат
+#[derive(Debug, Deserialize)]
+pub struct PredictQuery { pub model: Option<String>, pub top_k: Option<usize>, pub format: Option<ResponseFormat> }
+
+#[derive(Debug, Deserialize)]
+pub struct FormatQuery { pub format: Option<ResponseFormat> }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PredictSample {
+    pub id: i64,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PredictResponse<T = PredictItem> { pub predictions: Vec<T>, pub errors: Vec<PredictErrorItem> }
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PredictItem {
+    pub id: i64,
+    pub topics: Vec<TopicSentiment>,
+    /// Распределение классов тональности по `Sentiment::as_str`; пусто, если тональность определена эвристикой
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub sentiment_scores: BTreeMap<String, f32>,
+    /// Лучшие из не прошедших отбор топиков по убыванию уверенности; в ответ попадают только при `?top_k=`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rejected_topics: Vec<TopicScore>,
+}
+
+impl PredictItem {
+    pub fn new(id: i64, topics: Vec<TopicSentiment>) -> Self {
+        Self { id, topics, sentiment_scores: BTreeMap::new(), rejected_topics: Vec::new() }
+    }
+}
+
+/// Топик отзыва и тональность, с которой о нём написано
+#[derive(Debug, Serialize, Clone)]
+pub struct TopicSentiment {
+    pub topic: String,
+    /// Id топика в таксономии; нет у меток, которых в таксономии нет
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic_id: Option<i32>,
+    pub sentiment: Sentiment,
+    /// Уверенность в топике в [0, 1]: вероятность у ONNX и прокси, косинусная близость к центроиду у Native
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+    /// Оценка тональности в [-1, 1] по правилам ключевых слов (< 0 — отрицательная)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub polarity: Option<f32>,
+    /// Фрагменты отзыва, по которым определена тональность топика
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub spans: Vec<TextSpan>,
+    /// Какие предикторы ансамбля дали топик и его тональность
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<LabelProvenance>,
+}
+
+impl TopicSentiment {
+    pub fn new(topic: String, sentiment: Sentiment) -> Self {
+        Self { topic, topic_id: None, sentiment, score: None, polarity: None, spans: Vec::new(), provenance: None }
+    }
+
+    pub fn scored(topic: String, sentiment: Sentiment, score: f32) -> Self {
+        Self { score: Some(score), ..Self::new(topic, sentiment) }
+    }
+}
+
+/// Фрагмент текста; позиции — в символах
+#[derive(Debug, Serialize, Clone)]
+pub struct TextSpan {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Прежний формат serve.py: параллельные массивы `topics[i]` / `sentiments[i]`
+#[derive(Debug, Serialize)]
+pub struct LegacyPredictItem { pub id: i64, pub topics: Vec<String>, pub sentiments: Vec<String> }
+
+impl From<PredictItem> for LegacyPredictItem {
+    fn from(item: PredictItem) -> Self {
+        let (topics, sentiments) = item.topics.into_iter().map(|t| (t.topic, t.sentiment.as_label().to_string())).unzip();
+        Self { id: item.id, topics, sentiments }
+    }
+}
+
+/// Формат предсказаний в ответе: `structured` — `TopicSentiment`, `legacy` — как у serve.py
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat { Structured, Legacy }
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TopicScore { pub topic: String, pub score: f32 }
+
+/// Какие предикторы ансамбля дали топик и его тональность
+#[derive(Debug, Serialize, Clone)]
+pub struct LabelProvenance {
+    pub topic_sources: Vec<String>,
+    pub sentiment_sources: Vec<String>,
+}
+
+/// Ошибка предсказания; `id` отсутствует, если не выполнен весь запрос
+#[derive(Debug, Serialize, Clone)]
+pub struct PredictErrorItem {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub code: String,
+    pub message: String,
+}
+
+/// Исходный отзыв из загруженного файла с приложенными предсказаниями
 #[derive(Debug, Serialize, Deserialize)]
-pub struct PredictSample { pub id: i64, pub text: String }
+pub struct EnrichedSample {
+    #[serde(flatten)]
+    pub sample: PredictSample,
+    pub topics: Vec<String>,
+    pub sentiments: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnrichedDataset { pub data: Vec<EnrichedSample> }
+
+#[derive(Debug, Serialize)]
+pub struct UploadResponse { pub upload_id: i64, pub file_name: String, pub count: usize, pub errors: Vec<PredictErrorItem> }
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery { pub upload_id: Option<i64> }
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus { Queued, Running, Completed, Cancelled }
+
+#[derive(Debug, Serialize)]
+pub struct JobStatusResponse {
+    pub job_id: uuid::Uuid,
+    pub status: JobStatus,
+    pub total: usize,
+    pub processed: usize,
+    pub failed: usize,
+    pub progress: f64,
+    pub eta_seconds: Option<f64>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Описание активного предиктора (`/model/info`)
+#[derive(Debug, Serialize, Clone)]
+pub struct ModelInfo {
+    pub kind: String,
+    /// Версия из реестра моделей (`v43`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_sha256: Option<String>,
+    pub topic_labels: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vocab_size: Option<usize>,
+    pub loaded_at: chrono::DateTime<chrono::Utc>,
+    /// Предикторы цепочки fallback в порядке приоритета
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chain: Vec<ModelInfo>,
+    /// Теневые предикторы, сравниваемые с этим
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shadows: Vec<ModelInfo>,
+}
+
+/// Версия модели в `/models`
+#[derive(Debug, Serialize, Clone)]
+pub struct ModelVersionItem {
+    pub version: String,
+    pub default: bool,
+    pub info: ModelInfo,
+}
+
+impl ModelInfo {
+    pub fn new(kind: &str, loaded_at: chrono::DateTime<chrono::Utc>) -> Self {
+        Self {
+            kind: kind.to_string(),
+            version: None,
+            model_path: None,
+            endpoint: None,
+            file_sha256: None,
+            topic_labels: Vec::new(),
+            vocab_size: None,
+            loaded_at,
+            chain: Vec::new(),
+            shadows: Vec::new(),
+        }
+    }
+}
 
 #[derive(Debug, Serialize)]
-pub struct PredictResponse { pub predictions: Vec<PredictItem> }
+pub struct ReadinessCheck {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
 
 #[derive(Debug, Serialize)]
-pub struct PredictItem { pub id: i64, pub topics: Vec<String>, pub sentiments: Vec<String> }
+pub struct ReadinessResponse {
+    pub status: &'static str,
+    pub checks: std::collections::BTreeMap<&'static str, ReadinessCheck>,
+}
 
+/// Согласие теневого предиктора с основным по одной метке
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct LabelAgreement { pub agree: u64, pub disagree: u64, pub rate: f64 }
 
+/// Сводка сравнения теневого предиктора с основным (`/shadow/stats`)
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ShadowStatsItem {
+    pub shadow: String,
+    /// Отзывов, на которые ответили оба предиктора
+    pub compared: u64,
+    /// Совпали все топики и тональности
+    pub exact_matches: u64,
+    pub agreement_rate: f64,
+    pub failed: u64,
+    /// Не сравнивались из-за лимита фоновых вызовов
+    pub skipped: u64,
+    pub topics: BTreeMap<String, LabelAgreement>,
+    /// По тональности основного предиктора для общих топиков
+    pub sentiments: BTreeMap<String, LabelAgreement>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShadowStatsResponse { pub primary: String, pub shadows: Vec<ShadowStatsItem> }
+
+/// Отзыв, на котором теневой предиктор разошёлся с основным
+#[derive(Debug, Serialize, Clone)]
+pub struct ShadowDisagreement {
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub shadow: String,
+    pub id: i64,
+    pub text: String,
+    pub primary: PredictItem,
+    pub candidate: PredictItem,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShadowDisagreementsQuery { pub shadow: Option<String>, pub limit: Option<usize> }
+
+/// Загруженные правила ключевых слов
+#[derive(Debug, Serialize, Clone)]
+pub struct RulesInfo {
+    /// Путь к файлу или `builtin`
+    pub source: String,
+    pub rules: usize,
+    pub loaded_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RulesExplainRequest { pub text: String }
+
+/// Совпадение правила в тексте; позиции — в символах
+#[derive(Debug, Serialize, Clone)]
+pub struct FiredRule {
+    pub rule: String,
+    pub matched: String,
+    pub start: usize,
+    pub end: usize,
+    /// Перед совпадением стоит частица отрицания
+    pub negated: bool,
+    /// false — совпадение отброшено из-за отрицания (`on_negation: skip`)
+    pub applied: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    /// Тональность после учёта отрицания
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sentiment: Option<Sentiment>,
+    /// Вклад в оценку тональности с учётом отрицания, усилителей и противопоставления
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+    pub priority: i32,
+}
+
+/// Результат правил для текста и сработавшие правила (`POST /rules/explain`)
+#[derive(Debug, Serialize, Clone)]
+pub struct RulesExplanation {
+    /// Топики с тональностью по относящимся к ним фразам
+    pub topics: Vec<TopicSentiment>,
+    pub sentiment: Option<Sentiment>,
+    /// Оценка тональности в [-1, 1]; нет, если правила тональности не сработали
+    pub score: Option<f32>,
+    pub fired: Vec<FiredRule>,
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::Semaphore;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::domain::{JobStatus, JobStatusResponse, PredictSample};
+use crate::predict::{PredictOutput, Predictor};
+
+/// Фоновая задача пакетного предсказания
+pub struct Job {
+    pub id: Uuid,
+    total: usize,
+    processed: AtomicUsize,
+    failed: AtomicUsize,
+    cancel_requested: AtomicBool,
+    inner: Mutex<JobInner>,
+}
+
+struct JobInner {
+    status: JobStatus,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    started: Option<Instant>,
+    finished_at: Option<DateTime<Utc>>,
+    finished: Option<Instant>,
+    results: Option<Arc<PredictOutput>>,
+}
+
+impl Job {
+    fn new(total: usize) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            total,
+            processed: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            cancel_requested: AtomicBool::new(false),
+            inner: Mutex::new(JobInner {
+                status: JobStatus::Queued,
+                created_at: Utc::now(),
+                started_at: None,
+                started: None,
+                finished_at: None,
+                finished: None,
+                results: None,
+            }),
+        }
+    }
+
+    fn inner(&self) -> std::sync::MutexGuard<'_, JobInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn mark_running(&self) {
+        let mut inner = self.inner();
+        inner.status = JobStatus::Running;
+        inner.started_at = Some(Utc::now());
+        inner.started = Some(Instant::now());
+    }
+
+    fn finish(&self, status: JobStatus, results: PredictOutput) {
+        let mut inner = self.inner();
+        inner.status = status;
+        inner.finished_at = Some(Utc::now());
+        inner.finished = Some(Instant::now());
+        inner.results = Some(Arc::new(results));
+    }
+
+    pub fn status(&self) -> JobStatus {
+        self.inner().status
+    }
+
+    /// Результаты завершённой (или отменённой — частичные) задачи
+    pub fn results(&self) -> Option<Arc<PredictOutput>> {
+        self.inner().results.clone()
+    }
+
+    pub fn snapshot(&self) -> JobStatusResponse {
+        let inner = self.inner();
+        let processed = self.processed.load(Ordering::SeqCst);
+        let progress = if self.total == 0 { 1.0 } else { processed as f64 / self.total as f64 };
+
+        // ETA по средней скорости с момента старта
+        let eta_seconds = match (inner.status, inner.started) {
+            (JobStatus::Running, Some(started)) if processed > 0 => {
+                let per_sample = started.elapsed().as_secs_f64() / processed as f64;
+                Some(per_sample * (self.total - processed) as f64)
+            }
+            _ => None,
+        };
+
+        JobStatusResponse {
+            job_id: self.id,
+            status: inner.status,
+            total: self.total,
+            processed,
+            failed: self.failed.load(Ordering::SeqCst),
+            progress,
+            eta_seconds,
+            created_at: inner.created_at,
+            started_at: inner.started_at,
+            finished_at: inner.finished_at,
+        }
+    }
+
+    fn is_expired(&self, ttl: Duration) -> bool {
+        self.inner().finished.is_some_and(|f| f.elapsed() > ttl)
+    }
+}
+
+/// Реестр фоновых задач: ограниченное число одновременно работающих задач,
+/// обработка порциями с учётом прогресса и отмены
+pub struct JobManager {
+    jobs: Mutex<HashMap<Uuid, Arc<Job>>>,
+    workers: Arc<Semaphore>,
+    chunk_size: usize,
+    ttl: Duration,
+}
+
+impl JobManager {
+    pub fn new(workers: usize, chunk_size: usize, ttl: Duration) -> Self {
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+            workers: Arc::new(Semaphore::new(workers.max(1))),
+            chunk_size: chunk_size.max(1),
+            ttl,
+        }
+    }
+
+    fn jobs(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, Arc<Job>>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Ставит датасет в очередь; задача выполняется независимо от HTTP-соединения
+    /// `top_k` — сколько отклонённых топиков оставить в результате
+    pub fn submit(&self, predictor: Arc<dyn Predictor>, samples: Vec<PredictSample>, top_k: usize) -> Arc<Job> {
+        let job = Arc::new(Job::new(samples.len()));
+        {
+            let mut jobs = self.jobs();
+            jobs.retain(|_, j| !j.is_expired(self.ttl));
+            jobs.insert(job.id, job.clone());
+        }
+
+        let workers = self.workers.clone();
+        let chunk_size = self.chunk_size;
+        let task_job = job.clone();
+        tokio::spawn(async move {
+            let Ok(_permit) = workers.acquire_owned().await else { return };
+            run_job(task_job, predictor, samples, chunk_size, top_k).await;
+        });
+
+        info!("Job {} queued with {} samples", job.id, job.total);
+        job
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<Arc<Job>> {
+        self.jobs().get(id).cloned()
+    }
+
+    /// Запрашивает отмену; задача остановится перед следующей порцией
+    pub fn cancel(&self, id: &Uuid) -> Option<Arc<Job>> {
+        let job = self.get(id)?;
+        job.cancel_requested.store(true, Ordering::SeqCst);
+        Some(job)
+    }
+}
+
+async fn run_job(job: Arc<Job>, predictor: Arc<dyn Predictor>, samples: Vec<PredictSample>, chunk_size: usize, top_k: usize) {
+    let mut results = PredictOutput::default();
+    if job.cancel_requested.load(Ordering::SeqCst) {
+        job.finish(JobStatus::Cancelled, results);
+        return;
+    }
+
+    job.mark_running();
+    for chunk in samples.chunks(chunk_size) {
+        if job.cancel_requested.load(Ordering::SeqCst) {
+            info!("Job {} cancelled after {} samples", job.id, job.processed.load(Ordering::SeqCst));
+            job.finish(JobStatus::Cancelled, results);
+            return;
+        }
+        // Отказ на одной порции не прерывает задачу: её отзывы помечаются ошибкой
+        let mut output = match predictor.predict(chunk).await {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Job {}: chunk of {} samples failed: {}", job.id, chunk.len(), e);
+                PredictOutput::failed(chunk, e)
+            }
+        };
+        output.limit_rejected(top_k);
+        job.failed.fetch_add(output.errors.len(), Ordering::SeqCst);
+        job.processed.fetch_add(chunk.len(), Ordering::SeqCst);
+        results.extend(output);
+    }
+
+    info!("Job {} completed: {} predictions, {} errors", job.id, results.items.len(), results.errors.len());
+    job.finish(JobStatus::Completed, results);
+}
@@ -2,20 +2,30 @@
 mod api;
 mod config;
 mod domain;
+mod jobs;
+mod metrics;
 mod predict;
+mod storage;
+mod taxonomy;
 mod tokenizer;
 
 use actix_cors::Cors;
 use actix_files::Files;
+use actix_web::dev::Service;
 use actix_web::{web, App, HttpServer};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing_subscriber::EnvFilter;
-use tracing::info;
+use tracing::{error, info, warn};
 
 use crate::api::routes;
 use crate::config::Config;
-use crate::predict::{MockPredictor, Predictor, ProxyPredictor};
-use crate::predict::onnx_predictor::OnnxPredictor;
+use crate::jobs::JobManager;
+use crate::predict::reload::{ModelReloader, ReloadablePredictor};
+use crate::predict::rules::SharedRules;
+use crate::predict::{factory, self_test, Predictor};
+use crate::storage::ReviewStore;
+use crate::taxonomy::SharedTaxonomy;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -26,25 +36,119 @@ async fn main() -> std::io::Result<()> {
 
     info!("Starting Kabanchiki backend server");
 
-    // Загрузка конфигурации
-    let config = Config::from_env();
+    // Загрузка конфигурации: config.toml -> переменные окружения -> аргументы командной строки
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to load configuration: {:#}", e);
+            std::process::exit(2);
+        }
+    };
     info!("Configuration loaded: {:?}", config);
 
-    // Инициализация предиктора
-    let predictor: web::Data<dyn Predictor> = initialize_predictor(&config).await;
+    // Хранилище отзывов и таксономия топиков, к которой приводятся метки предикторов
+    let store = web::Data::new(ReviewStore::open(&config.db_path).map_err(std::io::Error::other)?);
+    let taxonomy = Arc::new(SharedTaxonomy::new(store.taxonomy().map_err(std::io::Error::other)?));
+    info!("Topic taxonomy loaded: {} topics", taxonomy.current().topics().len());
+
+    // Правила ключевых слов: тональность для предикторов без головы сентимента и топики Mock
+    let rules = match SharedRules::load(config.rules_path.clone()) {
+        Ok(rules) => Arc::new(rules),
+        Err(e) => {
+            error!("Failed to load keyword rules: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    if config.rules_watch_interval_secs > 0 {
+        rules.clone().spawn_watcher(Duration::from_secs(config.rules_watch_interval_secs));
+    }
+
+    // Инициализация предиктора и проверка на эталонных текстах
+    let initial = match factory::build(&config, &taxonomy, &rules, config.predictor_strict) {
+        Ok(predictor) => predictor,
+        Err(e) => {
+            error!("Failed to initialize predictor: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+    if config.startup_self_test
+        && let Err(e) = self_test::run_registry(&initial).await
+    {
+        if config.predictor_strict {
+            error!("Predictor self-test failed: {:#}", e);
+            std::process::exit(1);
+        }
+        warn!("Predictor self-test failed: {:#}", e);
+    }
+
+    // Активный предиктор подменяется атомарно при горячей перезагрузке модели
+    let reloadable = Arc::new(ReloadablePredictor::new(initial));
+    let reloader = Arc::new(ModelReloader::new(config.clone(), taxonomy.clone(), rules.clone(), reloadable.clone()));
+    if config.model_watch_interval_secs > 0 {
+        reloader.clone().spawn_watcher(Duration::from_secs(config.model_watch_interval_secs));
+    }
+    let predictor: web::Data<dyn Predictor> = web::Data::from(reloadable.clone() as Arc<dyn Predictor>);
+    let reloadable = web::Data::from(reloadable);
+    let reloader = web::Data::from(reloader);
+    let taxonomy = web::Data::from(taxonomy);
+    let rules = web::Data::from(rules);
+
+    // Фоновые задачи пакетного предсказания
+    let jobs = web::Data::new(JobManager::new(
+        config.job_workers,
+        config.job_chunk_size,
+        Duration::from_secs(config.job_ttl_secs),
+    ));
 
     // Создание HTTP сервера
     let server_host = config.server_host.clone();
     let server_port = config.server_port;
     let static_dir = config.static_dir.clone();
+    let app_config = web::Data::new(config.clone());
+    let json_limit = config.upload_limit_bytes;
 
     info!("Starting server on {}:{}", server_host, server_port);
 
     HttpServer::new(move || {
-        let cors = Cors::permissive();
+        let cors = if app_config.cors_permissive() {
+            Cors::permissive()
+        } else {
+            app_config
+                .cors_origins
+                .iter()
+                .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+                .allow_any_method()
+                .allow_any_header()
+                .max_age(3600)
+        };
         App::new()
+            .wrap_fn(|req, srv| {
+                let started = std::time::Instant::now();
+                let method = req.method().to_string();
+                let fut = srv.call(req);
+                async move {
+                    let res = fut.await?;
+                    // Шаблон маршрута (`/topics/{topic_id}/timeline`), а не сырой путь — без взрыва кардинальности
+                    let route = res.request().match_pattern().unwrap_or_else(|| "static".to_string());
+                    metrics::HTTP_LATENCY
+                        .with_label_values(&[&method, &route])
+                        .observe(started.elapsed().as_secs_f64());
+                    metrics::HTTP_REQUESTS
+                        .with_label_values(&[&method, &route, res.status().as_str()])
+                        .inc();
+                    Ok(res)
+                }
+            })
             .wrap(cors)
             .app_data(predictor.clone())
+            .app_data(reloadable.clone())
+            .app_data(reloader.clone())
+            .app_data(store.clone())
+            .app_data(taxonomy.clone())
+            .app_data(rules.clone())
+            .app_data(app_config.clone())
+            .app_data(jobs.clone())
+            .app_data(web::JsonConfig::default().limit(json_limit))
             .configure(routes)
             .service(Files::new("/", &static_dir).index_file("index.html"))
     })
@@ -52,30 +156,3 @@ async fn main() -> std::io::Result<()> {
     .run()
     .await
 }
-
-/// Инициализирует предиктор на основе конфигурации
-async fn initialize_predictor(config: &Config) -> web::Data<dyn Predictor> {
-    // Приоритет: Proxy -> ONNX -> Mock
-    if let Some(proxy_url) = &config.proxy_url {
-        info!("Using proxy predictor with URL: {}", proxy_url);
-        web::Data::from(Arc::new(ProxyPredictor::new(proxy_url.clone())) as Arc<dyn Predictor>)
-    } else {
-        let onnx_path = config.model_dir.join("v42_model.onnx");
-        if onnx_path.exists() {
-            info!("Attempting to initialize ONNX predictor with model: {:?}", onnx_path);
-            match OnnxPredictor::try_new(&onnx_path) {
-                Ok(predictor) => {
-                    info!("ONNX predictor initialized successfully");
-                    web::Data::from(Arc::new(predictor) as Arc<dyn Predictor>)
-                }
-                Err(e) => {
-                    eprintln!("Failed to initialize ONNX predictor: {:?}. Falling back to MockPredictor", e);
-                    web::Data::from(Arc::new(MockPredictor::new(config.model_dir.clone())) as Arc<dyn Predictor>)
-                }
-            }
-        } else {
-            info!("ONNX model not found at {:?}. Using MockPredictor", onnx_path);
-            web::Data::from(Arc::new(MockPredictor::new(config.model_dir.clone())) as Arc<dyn Predictor>)
-        }
-    }
-}
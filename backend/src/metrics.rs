@@ -0,0 +1,127 @@
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    exponential_buckets, register_histogram_vec, register_int_counter, register_int_counter_vec, Encoder,
+    HistogramVec, IntCounter, IntCounterVec, TextEncoder,
+};
+
+use crate::domain::PredictItem;
+use crate::predict::{PredictError, PredictOutput};
+
+pub static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("http_requests_total", "HTTP requests by route and status", &["method", "route", "status"])
+        .expect("register http_requests_total")
+});
+
+pub static HTTP_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!("http_request_duration_seconds", "HTTP request latency by route", &["method", "route"])
+        .expect("register http_request_duration_seconds")
+});
+
+pub static PREDICT_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "predictor_latency_seconds",
+        "Latency of a single Predictor::predict call by backend",
+        &["backend"],
+        exponential_buckets(0.001, 2.0, 16).expect("latency buckets")
+    )
+    .expect("register predictor_latency_seconds")
+});
+
+pub static PREDICT_BATCH_SIZE: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "predictor_batch_size",
+        "Number of samples per Predictor::predict call by backend",
+        &["backend"],
+        exponential_buckets(1.0, 2.0, 14).expect("batch buckets")
+    )
+    .expect("register predictor_batch_size")
+});
+
+pub static PREDICT_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("predictor_errors_total", "Failed samples by backend and error code", &["backend", "code"])
+        .expect("register predictor_errors_total")
+});
+
+pub static PREDICT_FALLBACKS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "predictor_fallback_samples_total",
+        "Samples handed over to the next predictor in the fallback chain",
+        &["from"]
+    )
+    .expect("register predictor_fallback_samples_total")
+});
+
+pub static PROXY_RETRIES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("proxy_retries_total", "Retried requests to the proxy predictor").expect("register proxy_retries_total")
+});
+
+pub static PREDICTED_TOPICS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("predicted_topics_total", "Predicted topic labels", &["topic"])
+        .expect("register predicted_topics_total")
+});
+
+pub static PREDICTED_SENTIMENTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("predicted_sentiments_total", "Predicted sentiment labels", &["sentiment"])
+        .expect("register predicted_sentiments_total")
+});
+
+pub static UNRESOLVED_TOPICS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "unresolved_topic_labels_total",
+        "Predicted topic labels missing from the taxonomy or pointing to inactive topics",
+        &["label", "reason"]
+    )
+    .expect("register unresolved_topic_labels_total")
+});
+
+pub static MODEL_RELOADS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("model_reloads_total", "Model hot-reload attempts by result", &["result"])
+        .expect("register model_reloads_total")
+});
+
+pub static RULES_RELOADS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("keyword_rules_reloads_total", "Keyword rules reload attempts by result", &["result"])
+        .expect("register keyword_rules_reloads_total")
+});
+
+pub static SHADOW_COMPARISONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "shadow_label_comparisons_total",
+        "Shadow vs primary label comparisons by shadow, dimension (topic/sentiment), label and outcome",
+        &["shadow", "dimension", "label", "outcome"]
+    )
+    .expect("register shadow_label_comparisons_total")
+});
+
+/// Латентность, размер батча и ошибки одного вызова предиктора
+pub fn observe_predict(backend: &str, batch_size: usize, started: Instant, result: &Result<PredictOutput, PredictError>) {
+    PREDICT_LATENCY.with_label_values(&[backend]).observe(started.elapsed().as_secs_f64());
+    PREDICT_BATCH_SIZE.with_label_values(&[backend]).observe(batch_size as f64);
+    match result {
+        Ok(output) => {
+            for (_, e) in &output.errors {
+                PREDICT_ERRORS.with_label_values(&[backend, e.code()]).inc();
+            }
+        }
+        Err(e) => PREDICT_ERRORS.with_label_values(&[backend, e.code()]).inc_by(batch_size as u64),
+    }
+}
+
+/// Распределение отданных клиентам меток — для отслеживания дрейфа
+pub fn record_labels(items: &[PredictItem]) {
+    for item in items {
+        for label in &item.topics {
+            PREDICTED_TOPICS.with_label_values(&[&label.topic]).inc();
+            PREDICTED_SENTIMENTS.with_label_values(&[label.sentiment.as_str()]).inc();
+        }
+    }
+}
+
+/// Текстовый формат Prometheus для `/metrics`
+pub fn render() -> Result<String, prometheus::Error> {
+    let mut buf = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
@@ -0,0 +1,218 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use tracing::warn;
+
+use crate::config::EnsembleStrategy;
+use crate::domain::{LabelProvenance, ModelInfo, PredictItem, PredictSample, Sentiment, TopicScore, TopicSentiment};
+use crate::predict::{PredictError, PredictOutput, Predictor, MAX_REJECTED_TOPICS};
+
+/// Участник ансамбля: имя для provenance, предиктор и вес голоса
+pub struct EnsembleMember {
+    pub name: String,
+    pub predictor: Arc<dyn Predictor>,
+    pub weight: f32,
+}
+
+/// Объединяет ответы нескольких предикторов по выбранной стратегии; у каждого топика
+/// в ответе указано, какие участники его дали и чья тональность выбрана
+pub struct EnsemblePredictor {
+    members: Vec<EnsembleMember>,
+    strategy: EnsembleStrategy,
+    /// Доля суммарного веса, которую должен набрать топик в стратегии `weighted`
+    vote_threshold: f32,
+    loaded_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl EnsemblePredictor {
+    pub fn new(members: Vec<EnsembleMember>, strategy: EnsembleStrategy, vote_threshold: f32) -> Self {
+        Self { members, strategy, vote_threshold, loaded_at: chrono::Utc::now() }
+    }
+
+    fn merge(&self, id: i64, answers: &[(&EnsembleMember, &PredictItem)]) -> PredictItem {
+        let votes = topic_votes(answers);
+        let total: f32 = answers.iter().map(|(m, _)| m.weight).sum();
+
+        let mut selected: Vec<&TopicVotes> = match self.strategy {
+            EnsembleStrategy::Union | EnsembleStrategy::RulesOverride => votes.iter().collect(),
+            EnsembleStrategy::Intersection => votes.iter().filter(|v| v.sources.len() == answers.len()).collect(),
+            EnsembleStrategy::Weighted => votes.iter().filter(|v| v.weight / total >= self.vote_threshold).collect(),
+        };
+        // Ни один топик не прошёл отбор — берём самый весомый, чтобы у отзыва был хотя бы один топик
+        if selected.is_empty()
+            && let Some(best) = votes.iter().reduce(|best, v| if v.weight > best.weight { v } else { best })
+        {
+            selected.push(best);
+        }
+
+        // В rules_override первый участник — правила: его топики идут первыми с его тональностью
+        let rules = match self.strategy {
+            EnsembleStrategy::RulesOverride => answers.first().filter(|(m, _)| std::ptr::eq(*m, &self.members[0])),
+            _ => None,
+        };
+        if let Some((rules, _)) = rules {
+            selected.sort_by_key(|v| !v.sources.iter().any(|(m, _)| std::ptr::eq(*m, *rules)));
+        }
+
+        let mut item = PredictItem::new(id, Vec::with_capacity(selected.len()));
+        item.sentiment_scores = sentiment_scores(answers);
+        let mut rejected: Vec<TopicScore> = votes
+            .iter()
+            .filter(|v| !selected.iter().any(|s| s.topic == v.topic))
+            .map(|v| TopicScore { topic: v.topic.clone(), score: v.weight / total })
+            .collect();
+        rejected.sort_by(|a, b| b.score.total_cmp(&a.score));
+        rejected.truncate(MAX_REJECTED_TOPICS);
+        item.rejected_topics = rejected;
+
+        for vote in selected {
+            let rule_sentiment = rules.and_then(|(rules, _)| vote.sources.iter().find(|(m, _)| std::ptr::eq(*m, *rules)));
+            let (sentiment, sentiment_sources) = match rule_sentiment {
+                Some((m, sentiment)) => (*sentiment, vec![m.name.clone()]),
+                None => vote.sentiment(),
+            };
+            item.topics.push(TopicSentiment {
+                provenance: Some(LabelProvenance {
+                    topic_sources: vote.sources.iter().map(|(m, _)| m.name.clone()).collect(),
+                    sentiment_sources,
+                }),
+                ..TopicSentiment::scored(vote.topic.clone(), sentiment, vote.weight / total)
+            });
+        }
+        item
+    }
+}
+
+/// Голоса за один топик: участники (в порядке ансамбля) с их тональностью
+struct TopicVotes<'a> {
+    topic: String,
+    /// Сумма `вес участника * уверенность в топике`; без оценки уверенность считается равной 1
+    weight: f32,
+    sources: Vec<(&'a EnsembleMember, Sentiment)>,
+}
+
+impl TopicVotes<'_> {
+    /// Тональность взвешенным большинством; при равенстве — раньше стоящего участника
+    fn sentiment(&self) -> (Sentiment, Vec<String>) {
+        let mut weights: Vec<(Sentiment, f32)> = Vec::new();
+        for (member, sentiment) in &self.sources {
+            match weights.iter_mut().find(|(s, _)| s == sentiment) {
+                Some((_, w)) => *w += member.weight,
+                None => weights.push((*sentiment, member.weight)),
+            }
+        }
+        let winner = weights
+            .iter()
+            .copied()
+            .reduce(|best, w| if w.1 > best.1 { w } else { best })
+            .map(|(s, _)| s)
+            .unwrap_or(Sentiment::Neutral);
+        let sources = self.sources.iter().filter(|(_, s)| *s == winner).map(|(m, _)| m.name.clone()).collect();
+        (winner, sources)
+    }
+}
+
+/// Топики всех ответивших участников в порядке первого появления
+fn topic_votes<'a>(answers: &[(&'a EnsembleMember, &'a PredictItem)]) -> Vec<TopicVotes<'a>> {
+    let mut votes: Vec<TopicVotes<'a>> = Vec::new();
+    for (member, item) in answers {
+        for label in &item.topics {
+            let idx = match votes.iter().position(|v| v.topic == label.topic) {
+                Some(idx) => idx,
+                None => {
+                    votes.push(TopicVotes { topic: label.topic.clone(), weight: 0.0, sources: Vec::new() });
+                    votes.len() - 1
+                }
+            };
+            votes[idx].weight += member.weight * label.score.unwrap_or(1.0);
+            votes[idx].sources.push((member, label.sentiment));
+        }
+    }
+    votes
+}
+
+/// Взвешенное среднее распределений тональности участников, которые их прислали
+fn sentiment_scores(answers: &[(&EnsembleMember, &PredictItem)]) -> BTreeMap<String, f32> {
+    let mut scores: BTreeMap<String, f32> = BTreeMap::new();
+    let mut total = 0.0;
+    for (member, item) in answers.iter().filter(|(_, item)| !item.sentiment_scores.is_empty()) {
+        total += member.weight;
+        for (label, p) in &item.sentiment_scores {
+            *scores.entry(label.clone()).or_default() += member.weight * p;
+        }
+    }
+    scores.values_mut().for_each(|p| *p /= total);
+    scores
+}
+
+#[async_trait]
+impl Predictor for EnsemblePredictor {
+    async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        let results = join_all(self.members.iter().map(|m| m.predictor.predict(samples))).await;
+
+        let mut answers: HashMap<i64, Vec<(&EnsembleMember, &PredictItem)>> = HashMap::with_capacity(samples.len());
+        let mut errors: HashMap<i64, PredictError> = HashMap::new();
+        let mut last_error = None;
+        for (member, result) in self.members.iter().zip(&results) {
+            match result {
+                Ok(output) => {
+                    for item in &output.items {
+                        answers.entry(item.id).or_default().push((member, item));
+                    }
+                    for (id, e) in &output.errors {
+                        errors.entry(*id).or_insert_with(|| e.clone().context(&member.name));
+                    }
+                }
+                Err(e) => {
+                    warn!("Ensemble member {} failed: {}", member.name, e);
+                    last_error = Some(e.clone().context(&member.name));
+                }
+            }
+        }
+        if answers.is_empty()
+            && let Some(e) = last_error.clone()
+        {
+            return Err(e);
+        }
+
+        // Отзыв без единого ответа — ошибка первого отказавшего участника
+        let mut output = PredictOutput::default();
+        for sample in samples {
+            match answers.get(&sample.id) {
+                Some(item_answers) => output.items.push(self.merge(sample.id, item_answers)),
+                None => {
+                    let e = errors.remove(&sample.id).or_else(|| last_error.clone()).unwrap_or_else(|| {
+                        PredictError::MalformedResponse("no ensemble member answered the sample".to_string())
+                    });
+                    output.errors.push((sample.id, e));
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    fn describe(&self) -> ModelInfo {
+        let chain: Vec<ModelInfo> = self
+            .members
+            .iter()
+            .map(|m| ModelInfo { version: Some(m.name.clone()), ..m.predictor.describe() })
+            .collect();
+        let mut topic_labels: Vec<String> = Vec::new();
+        for label in chain.iter().flat_map(|info| &info.topic_labels) {
+            if !topic_labels.contains(label) {
+                topic_labels.push(label.clone());
+            }
+        }
+        ModelInfo { topic_labels, chain, ..ModelInfo::new("ensemble", self.loaded_at) }
+    }
+
+    /// Ансамбль готов, только если готовы все участники: без одного из них меняется смысл объединения
+    async fn check_ready(&self) -> Result<(), PredictError> {
+        for member in &self.members {
+            member.predictor.check_ready().await.map_err(|e| e.context(&member.name))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use tracing::{info, warn};
+
+use crate::config::{Config, PredictorKind};
+use crate::predict::ensemble::{EnsembleMember, EnsemblePredictor};
+use crate::predict::fallback::FallbackPredictor;
+use crate::predict::native_predictor::NativePredictor;
+use crate::predict::onnx_predictor::{OnnxPredictor, TOPIC_LABELS};
+use crate::predict::proxy_predictor::{ProxyPredictor, ProxySettings};
+use crate::predict::registry::{self, ModelRegistry, ModelVersion};
+use crate::predict::shadow::ShadowPredictor;
+use crate::predict::rules::SharedRules;
+use crate::predict::taxonomy::TaxonomyPredictor;
+use crate::predict::{MockPredictor, Predictor};
+use crate::taxonomy::SharedTaxonomy;
+use crate::tokenizer::WordPieceTokenizer;
+
+type NamedPredictor = (String, Arc<dyn Predictor>);
+
+/// Имя версии ансамбля в реестре
+const ENSEMBLE: &str = "ensemble";
+
+/// Собирает реестр версий модели из `MODEL_DIR`
+///
+/// Версия по умолчанию — `default_model` или последняя найденная. Прокси-сервис не выбирает
+/// версию, поэтому подключается только к цепочке версии по умолчанию. Если ни одной версии
+/// нет, реестр содержит единственную версию `default` (Proxy или Mock).
+/// `strict` переопределяет `config.predictor_strict` (горячая перезагрузка всегда строгая).
+/// Метки каждой версии приводятся к `taxonomy`; тональность без модели дают `rules`.
+pub fn build(
+    config: &Config,
+    taxonomy: &Arc<SharedTaxonomy>,
+    rules: &Arc<SharedRules>,
+    strict: bool,
+) -> Result<ModelRegistry> {
+    let resolved = |predictor: Arc<dyn Predictor>| -> Arc<dyn Predictor> {
+        Arc::new(TaxonomyPredictor::new(predictor, taxonomy.clone()))
+    };
+
+    let mut versions = registry::discover(config)?;
+    if versions.is_empty() {
+        info!("No versioned models found in {:?}", config.model_dir);
+        versions.push(ModelVersion::unversioned(config));
+    }
+
+    let has_ensemble = !config.ensemble_members.is_empty();
+    let default = match &config.default_model {
+        Some(name) if versions.iter().any(|v| &v.name == name) => name.clone(),
+        Some(name) if name == ENSEMBLE && has_ensemble => name.clone(),
+        Some(name) => bail!(
+            "default model {} not found in {:?} (available: {})",
+            name,
+            config.model_dir,
+            versions.iter().map(|v| v.name.as_str()).collect::<Vec<_>>().join(", ")
+        ),
+        None => versions.last().map(|v| v.name.clone()).expect("at least one model version"),
+    };
+    // Прокси подключается к версии по умолчанию, а если это ансамбль — к последней версии
+    let primary_version = match versions.iter().find(|v| v.name == default) {
+        Some(version) => version.name.clone(),
+        None => versions.last().map(|v| v.name.clone()).expect("at least one model version"),
+    };
+
+    let mut models = BTreeMap::new();
+    for version in &versions {
+        let is_primary = version.name == primary_version;
+        if !is_primary && !supports(config.predictor, version) {
+            info!("Skipping model {}: no files for {:?} predictor", version.name, config.predictor);
+            continue;
+        }
+        let predictor = build_version(config, version, rules, is_primary, strict)
+            .with_context(|| format!("failed to load model {}", version.name))?;
+        models.insert(version.name.clone(), resolved(predictor));
+    }
+
+    if has_ensemble {
+        let mut members = Vec::with_capacity(config.ensemble_members.len());
+        for (name, weight) in config.ensemble_weights()? {
+            let predictor = match name.as_str() {
+                "mock" => resolved(build_mock(rules)),
+                name => models.get(name).cloned().with_context(|| {
+                    format!("ensemble member {} not found (available: {})", name, models.keys().cloned().collect::<Vec<_>>().join(", "))
+                })?,
+            };
+            members.push(EnsembleMember { name, predictor, weight });
+        }
+        info!("Model {}: {:?} of {}", ENSEMBLE, config.ensemble_strategy, config.ensemble_members.join(", "));
+        let ensemble = EnsemblePredictor::new(members, config.ensemble_strategy, config.ensemble_vote_threshold);
+        models.insert(ENSEMBLE.to_string(), resolved(Arc::new(ensemble)));
+    }
+    info!("Model registry: {} (default {})", models.keys().cloned().collect::<Vec<_>>().join(", "), default);
+
+    let mut shadow_stats = None;
+    if !config.shadow_models.is_empty() {
+        let mut shadows = Vec::with_capacity(config.shadow_models.len());
+        for name in &config.shadow_models {
+            let shadow = match name.as_str() {
+                name if name == default => bail!("shadow model {} is the default model", name),
+                "mock" => resolved(build_mock(rules)),
+                name => models.get(name).cloned().with_context(|| {
+                    format!("shadow model {} not found (available: {})", name, models.keys().cloned().collect::<Vec<_>>().join(", "))
+                })?,
+            };
+            shadows.push((name.clone(), shadow));
+        }
+        info!("Model {}: shadowed by {}", default, config.shadow_models.join(", "));
+        let primary = models.remove(&default).context("default model is not loaded")?;
+        let shadow = ShadowPredictor::new(primary, shadows, config.shadow_max_in_flight);
+        shadow_stats = Some(shadow.stats().clone());
+        models.insert(default.clone(), Arc::new(shadow));
+    }
+
+    let registry = ModelRegistry::new(models, default)?;
+    Ok(match shadow_stats {
+        Some(stats) => registry.with_shadow_stats(stats),
+        None => registry,
+    })
+}
+
+/// Есть ли у версии файлы для явно выбранного предиктора
+fn supports(kind: PredictorKind, version: &ModelVersion) -> bool {
+    match kind {
+        PredictorKind::Onnx => version.onnx_path.is_some(),
+        PredictorKind::Native => version.artifacts_path.is_some(),
+        PredictorKind::Proxy => false,
+        PredictorKind::Auto | PredictorKind::Mock => true,
+    }
+}
+
+/// Собирает предиктор одной версии
+///
+/// `auto`: цепочка Proxy -> ONNX -> Native (TF-IDF/SVD) -> Mock из доступных предикторов; при отказе
+/// прокси или ONNX запрос обрабатывает следующий предиктор цепочки. Явно выбранный предиктор
+/// дополняется локальным fallback, а в strict-режиме используется один и обязан загрузиться.
+fn build_version(
+    config: &Config,
+    version: &ModelVersion,
+    rules: &Arc<SharedRules>,
+    with_proxy: bool,
+    strict: bool,
+) -> Result<Arc<dyn Predictor>> {
+    let mut chain: Vec<NamedPredictor> = Vec::new();
+
+    match config.predictor {
+        PredictorKind::Auto => {
+            if with_proxy && config.proxy_url.is_some() {
+                push_or_fail(&mut chain, "proxy", build_proxy(config), strict)?;
+            }
+            match &version.onnx_path {
+                Some(_) => push_or_fail(&mut chain, "onnx", build_onnx(config, version, rules), strict)?,
+                None => info!("No ONNX model for {}", version.name),
+            }
+            chain.push(build_local(version, rules, strict)?);
+        }
+        kind => {
+            let (name, predictor) = match kind {
+                PredictorKind::Proxy => ("proxy", build_proxy(config)),
+                PredictorKind::Onnx => ("onnx", build_onnx(config, version, rules)),
+                PredictorKind::Native => ("native", build_native(version, rules)),
+                _ => ("mock", Ok(build_mock(rules))),
+            };
+            push_or_fail(&mut chain, name, predictor, strict)?;
+            if !strict && !matches!(kind, PredictorKind::Native | PredictorKind::Mock) {
+                chain.push(build_local(version, rules, false)?);
+            } else if chain.is_empty() {
+                chain.push(("mock".to_string(), build_mock(rules)));
+            }
+        }
+    }
+
+    if chain.len() == 1 {
+        let (name, predictor) = chain.remove(0);
+        info!("Model {}: using {} predictor", version.name, name);
+        return Ok(predictor);
+    }
+    info!(
+        "Model {}: predictor fallback chain {}",
+        version.name,
+        chain.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(" -> ")
+    );
+    Ok(Arc::new(FallbackPredictor::new(chain)))
+}
+
+/// Добавляет загруженный предиктор в цепочку; ошибка загрузки фатальна только в strict-режиме
+fn push_or_fail(
+    chain: &mut Vec<NamedPredictor>,
+    name: &str,
+    predictor: Result<Arc<dyn Predictor>>,
+    strict: bool,
+) -> Result<()> {
+    match predictor {
+        Ok(predictor) => {
+            info!("{} predictor initialized successfully", name);
+            chain.push((name.to_string(), predictor));
+        }
+        Err(e) if strict => return Err(e.context(format!("failed to initialize {} predictor", name))),
+        Err(e) => warn!("Failed to initialize {} predictor: {:#}. Falling back", name, e),
+    }
+    Ok(())
+}
+
+fn build_proxy(config: &Config) -> Result<Arc<dyn Predictor>> {
+    let proxy_url = config.proxy_url.clone().context("predict_url is not configured")?;
+    let settings = ProxySettings {
+        request_timeout: Duration::from_millis(config.proxy_timeout_ms),
+        connect_timeout: Duration::from_millis(config.proxy_connect_timeout_ms),
+        retries: config.proxy_retries,
+        backoff: Duration::from_millis(config.proxy_backoff_ms),
+        breaker_threshold: config.breaker_threshold,
+        breaker_cooldown: Duration::from_secs(config.breaker_cooldown_secs),
+        chunk_size: config.proxy_chunk_size,
+        concurrency: config.proxy_concurrency,
+    };
+    info!("Using proxy predictor with URL: {}", proxy_url);
+    Ok(Arc::new(ProxyPredictor::new(proxy_url, settings)?))
+}
+
+fn build_onnx(config: &Config, version: &ModelVersion, rules: &Arc<SharedRules>) -> Result<Arc<dyn Predictor>> {
+    let onnx_path = version.onnx_path.as_ref().context("model has no ONNX file")?;
+    if !onnx_path.exists() {
+        bail!("ONNX model not found at {:?}", onnx_path);
+    }
+    info!("Attempting to initialize ONNX predictor with model: {:?}", onnx_path);
+    let tokenizer = WordPieceTokenizer::from_model_dir(&version.tokenizer_dir, version.tokenizer_lowercase)?;
+    let labels = version
+        .topic_labels
+        .clone()
+        .unwrap_or_else(|| TOPIC_LABELS.iter().map(|t| t.to_string()).collect());
+    let predictor = OnnxPredictor::try_new(
+        onnx_path,
+        tokenizer,
+        labels,
+        config.onnx_batch_size,
+        version.topic_threshold,
+        rules.clone(),
+    )?;
+    Ok(Arc::new(predictor))
+}
+
+/// Native (TF-IDF/SVD из экспортированных артефактов)
+fn build_native(version: &ModelVersion, rules: &Arc<SharedRules>) -> Result<Arc<dyn Predictor>> {
+    let artifacts_path = version.artifacts_path.as_ref().context("model has no native artifacts")?;
+    if !artifacts_path.exists() {
+        bail!("native artifacts not found at {:?}", artifacts_path);
+    }
+    Ok(Arc::new(NativePredictor::try_new(artifacts_path, rules.clone())?))
+}
+
+fn build_mock(rules: &Arc<SharedRules>) -> Arc<dyn Predictor> {
+    Arc::new(MockPredictor::new(rules.clone()))
+}
+
+/// Локальный конец цепочки: Native, если есть артефакты, иначе Mock
+fn build_local(version: &ModelVersion, rules: &Arc<SharedRules>, strict: bool) -> Result<NamedPredictor> {
+    if version.artifacts_path.is_none() {
+        info!("No native artifacts for {}. Using MockPredictor", version.name);
+        return Ok(("mock".to_string(), build_mock(rules)));
+    }
+    match build_native(version, rules) {
+        Ok(predictor) => {
+            info!("native predictor initialized successfully");
+            Ok(("native".to_string(), predictor))
+        }
+        Err(e) if strict => Err(e.context("failed to initialize native predictor")),
+        Err(e) => {
+            warn!("Failed to initialize native predictor: {:#}. Falling back to MockPredictor", e);
+            Ok(("mock".to_string(), build_mock(rules)))
+        }
+    }
+}
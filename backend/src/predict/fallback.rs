@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tracing::warn;
+
+use crate::domain::{ModelInfo, PredictItem, PredictSample};
+use crate::metrics;
+use crate::predict::{PredictError, PredictOutput, Predictor};
+
+/// Цепочка предикторов по приоритету (Proxy -> ONNX -> Native/Mock), проверяемая на каждом запросе:
+/// отзывы, которые не смог обработать очередной предиктор, передаются следующему
+pub struct FallbackPredictor {
+    chain: Vec<(String, Arc<dyn Predictor>)>,
+    loaded_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl FallbackPredictor {
+    pub fn new(chain: Vec<(String, Arc<dyn Predictor>)>) -> Self {
+        Self { chain, loaded_at: chrono::Utc::now() }
+    }
+}
+
+#[async_trait]
+impl Predictor for FallbackPredictor {
+    async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        let mut pending: Vec<PredictSample> = samples.to_vec();
+        let mut done: HashMap<i64, PredictItem> = HashMap::with_capacity(samples.len());
+        let mut errors: HashMap<i64, PredictError> = HashMap::new();
+        let mut last_error = None;
+
+        for (name, predictor) in &self.chain {
+            if pending.is_empty() {
+                break;
+            }
+            match predictor.predict(&pending).await {
+                Ok(output) => {
+                    errors = output.errors.into_iter().collect();
+                    done.extend(output.items.into_iter().map(|p| (p.id, p)));
+                    // Отзыв без ответа и без ошибки — тоже сбой предиктора, его пробует следующий
+                    for sample in &pending {
+                        if !done.contains_key(&sample.id) && !errors.contains_key(&sample.id) {
+                            let message = format!("{} predictor returned no result for id {}", name, sample.id);
+                            errors.insert(sample.id, PredictError::MalformedResponse(message));
+                        }
+                    }
+                    if !errors.is_empty() {
+                        warn!("{} predictor failed on {} samples, falling back", name, errors.len());
+                        metrics::PREDICT_FALLBACKS.with_label_values(&[name]).inc_by(errors.len() as u64);
+                    }
+                    pending.retain(|s| errors.contains_key(&s.id));
+                }
+                Err(e) => {
+                    warn!("{} predictor failed: {}. Falling back", name, e);
+                    metrics::PREDICT_FALLBACKS.with_label_values(&[name]).inc_by(pending.len() as u64);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        // Весь запрос не обработал ни один предиктор
+        if done.is_empty() && errors.is_empty() && !samples.is_empty()
+            && let Some(e) = last_error
+        {
+            return Err(e);
+        }
+
+        let mut output = PredictOutput::default();
+        for sample in samples {
+            if let Some(item) = done.remove(&sample.id) {
+                output.items.push(item);
+            } else if let Some(e) = errors.remove(&sample.id).or_else(|| last_error.clone()) {
+                output.errors.push((sample.id, e));
+            }
+        }
+        Ok(output)
+    }
+
+    fn describe(&self) -> ModelInfo {
+        let chain: Vec<ModelInfo> = self.chain.iter().map(|(_, p)| p.describe()).collect();
+        ModelInfo {
+            // Прокси не сообщает метки, берём их у первого предиктора, который их знает
+            topic_labels: chain.iter().map(|m| &m.topic_labels).find(|l| !l.is_empty()).cloned().unwrap_or_default(),
+            chain,
+            ..ModelInfo::new("fallback", self.loaded_at)
+        }
+    }
+
+    /// Готова, только если готовы все предикторы цепочки (в том числе внешний сервис)
+    async fn check_ready(&self) -> Result<(), PredictError> {
+        for (name, predictor) in &self.chain {
+            predictor.check_ready().await.map_err(|e| e.context(name))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Sentiment, TopicSentiment};
+
+    /// Отвечает на все отзывы, кроме `omit`; топик ответа — имя предиктора
+    struct Stub {
+        name: &'static str,
+        omit: Option<i64>,
+    }
+
+    #[async_trait]
+    impl Predictor for Stub {
+        async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+            let items = samples
+                .iter()
+                .filter(|s| Some(s.id) != self.omit)
+                .map(|s| PredictItem::new(s.id, vec![TopicSentiment::new(self.name.to_string(), Sentiment::Neutral)]))
+                .collect();
+            Ok(PredictOutput::ok(items))
+        }
+
+        fn describe(&self) -> ModelInfo {
+            ModelInfo::new(self.name, chrono::Utc::now())
+        }
+    }
+
+    fn chain(stubs: Vec<Stub>) -> FallbackPredictor {
+        FallbackPredictor::new(stubs.into_iter().map(|s| (s.name.to_string(), Arc::new(s) as Arc<dyn Predictor>)).collect())
+    }
+
+    fn samples() -> Vec<PredictSample> {
+        (1..=3).map(|id| PredictSample { id, text: format!("отзыв {}", id), date: None, region: None }).collect()
+    }
+
+    #[tokio::test]
+    async fn omitted_sample_goes_to_next_predictor() {
+        let fallback = chain(vec![Stub { name: "first", omit: Some(2) }, Stub { name: "second", omit: None }]);
+        let output = fallback.predict(&samples()).await.expect("chain answered");
+        let answered: Vec<(i64, &str)> = output.items.iter().map(|i| (i.id, i.topics[0].topic.as_str())).collect();
+        assert_eq!(answered, [(1, "first"), (2, "second"), (3, "first")]);
+        assert!(output.errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sample_omitted_by_last_predictor_is_reported() {
+        let fallback = chain(vec![Stub { name: "only", omit: Some(2) }]);
+        let output = fallback.predict(&samples()).await.expect("chain answered");
+        assert_eq!(output.items.iter().map(|i| i.id).collect::<Vec<_>>(), [1, 3]);
+        assert!(matches!(output.errors.as_slice(), [(2, PredictError::MalformedResponse(_))]));
+    }
+}
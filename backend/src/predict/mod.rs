@@ -1,123 +1,171 @@
-use std::path::PathBuf;
-use crate::domain::{PredictItem, PredictSample};
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use crate::domain::{ModelInfo, PredictErrorItem, PredictItem, PredictSample};
+use crate::metrics;
+use crate::predict::rules::SharedRules;
+use sha2::{Digest, Sha256};
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
 use async_trait::async_trait;
 
+pub mod ensemble;
+pub mod factory;
+pub mod fallback;
+pub mod native_predictor;
 pub mod onnx_predictor;
+pub mod proxy_predictor;
+pub mod registry;
+pub mod reload;
+pub mod rules;
+pub mod self_test;
+pub mod shadow;
+pub mod taxonomy;
+
+/// Ошибка предсказания: всего запроса (`Err` из `predict`) или отдельного отзыва
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum PredictError {
+    #[error("predictor unavailable: {0}")]
+    Unavailable(String),
+    #[error("predictor timed out: {0}")]
+    Timeout(String),
+    #[error("malformed predictor response: {0}")]
+    MalformedResponse(String),
+    #[error("model error: {0}")]
+    Model(String),
+}
 
-#[async_trait]
-pub trait Predictor: Send + Sync {
-    async fn predict(&self, samples: &Vec<PredictSample>) -> Vec<PredictItem>;
+impl PredictError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PredictError::Unavailable(_) => "unavailable",
+            PredictError::Timeout(_) => "timeout",
+            PredictError::MalformedResponse(_) => "malformed_response",
+            PredictError::Model(_) => "model_error",
+        }
+    }
+
+    /// Та же ошибка с уточнением источника в сообщении
+    pub fn context(self, context: &str) -> Self {
+        match self {
+            PredictError::Unavailable(m) => PredictError::Unavailable(format!("{}: {}", context, m)),
+            PredictError::Timeout(m) => PredictError::Timeout(format!("{}: {}", context, m)),
+            PredictError::MalformedResponse(m) => PredictError::MalformedResponse(format!("{}: {}", context, m)),
+            PredictError::Model(m) => PredictError::Model(format!("{}: {}", context, m)),
+        }
+    }
+
+    /// Элемент массива `errors` в ответе API (`id = None` — ошибка всего запроса)
+    pub fn to_item(&self, id: Option<i64>) -> PredictErrorItem {
+        PredictErrorItem { id, code: self.code().to_string(), message: self.to_string() }
+    }
 }
 
-/// Mock predictor для тестирования и fallback
-pub struct MockPredictor {
-    _model_dir: PathBuf,
+impl ResponseError for PredictError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PredictError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            PredictError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            PredictError::MalformedResponse(_) => StatusCode::BAD_GATEWAY,
+            PredictError::Model(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(serde_json::json!({ "errors": [self.to_item(None)] }))
+    }
 }
 
-impl MockPredictor {
-    pub fn new(model_dir: PathBuf) -> Self { 
-        Self { _model_dir: model_dir } 
+/// Результат пакетного предсказания: успешные отзывы и ошибки по отдельным отзывам
+#[derive(Debug, Default)]
+pub struct PredictOutput {
+    pub items: Vec<PredictItem>,
+    pub errors: Vec<(i64, PredictError)>,
+}
+
+/// Сколько отклонённых топиков предикторы сохраняют для `?top_k=`
+pub const MAX_REJECTED_TOPICS: usize = 10;
+
+impl PredictOutput {
+    pub fn ok(items: Vec<PredictItem>) -> Self {
+        Self { items, errors: Vec::new() }
+    }
+
+    /// Все отзывы пакета завершились одной и той же ошибкой
+    pub fn failed(samples: &[PredictSample], error: PredictError) -> Self {
+        Self { items: Vec::new(), errors: samples.iter().map(|s| (s.id, error.clone())).collect() }
+    }
+
+    pub fn extend(&mut self, other: PredictOutput) {
+        self.items.extend(other.items);
+        self.errors.extend(other.errors);
+    }
+
+    /// Оставляет не больше `top_k` отклонённых топиков у каждого отзыва
+    pub fn limit_rejected(&mut self, top_k: usize) {
+        for item in &mut self.items {
+            item.rejected_topics.truncate(top_k);
+        }
+    }
+
+    pub fn error_items(&self) -> Vec<PredictErrorItem> {
+        self.errors.iter().map(|(id, e)| e.to_item(Some(*id))).collect()
     }
 }
 
 #[async_trait]
-impl Predictor for MockPredictor {
-    async fn predict(&self, samples: &Vec<PredictSample>) -> Vec<PredictItem> {
-        samples
-            .iter()
-            .map(|s| {
-                let text_l = s.text.to_lowercase();
-
-                // Topics by simple keyword matching
-                let mut topics: Vec<String> = Vec::new();
-                let mut push_if = |kw: &str, topic: &str| {
-                    if text_l.contains(kw) && !topics.iter().any(|t| t == topic) {
-                        topics.push(topic.to_string());
-                    }
-                };
-                push_if("обслужив", "Обслуживание");
-                push_if("мобильное прилож", "Мобильное приложение");
-                push_if("онлайн-банк", "Онлайн-банк");
-                push_if("сайт", "Сайт");
-                push_if("ипотек", "Ипотека");
-                push_if("кредит", "Кредит");
-                push_if("карт", "Карта");
-                push_if("терминал", "Терминал");
-                push_if("поддержк", "Поддержка");
-
-                if topics.is_empty() {
-                    topics.push("Обслуживание".to_string());
-                }
-
-                // Sentiment detection (robust keyword sets, negative has precedence)
-                let has_explicit_pos = text_l.contains("положительно");
-                let has_explicit_neg = text_l.contains("отрицательно");
-                let has_explicit_neu = text_l.contains("нейтрально");
-
-                let neg_kw = [
-                    "непонрав", "не понрав", "зависает", "зависа", "долго", "плохо", "ужасн",
-                    "медлен", "лома", "обман",
-                ];
-                let pos_kw = [
-                    "понрав", "нрав", "быстро", "отлично", "хорошо", "рекоменд", "удоб",
-                ];
-
-                let has_neg = has_explicit_neg || neg_kw.iter().any(|k| text_l.contains(k));
-                let has_pos = has_explicit_pos || pos_kw.iter().any(|k| text_l.contains(k));
-
-                let sentiment = if has_neg {
-                    "отрицательно"
-                } else if has_pos {
-                    "положительно"
-                } else if has_explicit_neu {
-                    "нейтрально"
-                } else {
-                    "нейтрально"
-                };
-
-                PredictItem {
-                    id: s.id,
-                    topics: topics.clone(),
-                    sentiments: vec![sentiment.to_string(); topics.len()],
-                }
-            })
-            .collect()
+pub trait Predictor: Send + Sync {
+    /// `Err` — запрос не выполнен целиком; ошибки отдельных отзывов возвращаются в `PredictOutput::errors`
+    async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError>;
+
+    /// Описание загруженной модели для `/model/info`
+    fn describe(&self) -> ModelInfo;
+
+    /// Готовность обслуживать запросы (`/readyz`): модель загружена, внешний сервис доступен
+    async fn check_ready(&self) -> Result<(), PredictError> {
+        Ok(())
     }
 }
 
-// Proxy predictor calls external Python service compatible with our /predict schema
-pub struct ProxyPredictor { client: reqwest::Client, url: String }
+/// SHA-256 файла модели в hex
+pub(crate) fn file_sha256(path: &Path) -> anyhow::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
 
-impl ProxyPredictor {
-    pub fn new(url: String) -> Self { Self { client: reqwest::Client::new(), url } }
+/// Mock predictor для тестирования и fallback: топики и их тональность по правилам ключевых слов
+pub struct MockPredictor {
+    rules: Arc<SharedRules>,
+    loaded_at: chrono::DateTime<chrono::Utc>,
 }
 
-#[async_trait]
-impl Predictor for ProxyPredictor {
-    async fn predict(&self, samples: &Vec<PredictSample>) -> Vec<PredictItem> {
-        let body = serde_json::json!({ "data": samples });
-        match self.client.post(&self.url).json(&body).send().await {
-            Ok(resp) => match resp.json::<serde_json::Value>().await {
-                Ok(json) => json
-                    .get("predictions")
-                    .and_then(|v| v.as_array())
-                    .map(|arr| {
-                        arr.iter()
-                            .filter_map(|it| {
-                                Some(PredictItem {
-                                    id: it.get("id")?.as_i64()?,
-                                    topics: it.get("topics")?.as_array()?.iter().filter_map(|t| t.as_str().map(|s| s.to_string())).collect(),
-                                    sentiments: it.get("sentiments")?.as_array()?.iter().filter_map(|t| t.as_str().map(|s| s.to_string())).collect(),
-                                })
-                            })
-                            .collect::<Vec<_>>()
-                    })
-                    .unwrap_or_default(),
-                Err(_) => vec![],
-            },
-            Err(_) => vec![],
-        }
+impl MockPredictor {
+    pub fn new(rules: Arc<SharedRules>) -> Self {
+        Self { rules, loaded_at: chrono::Utc::now() }
     }
 }
 
+#[async_trait]
+impl Predictor for MockPredictor {
+    async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        let started = std::time::Instant::now();
+        let rules = self.rules.current();
+        let items = samples.iter().map(|s| PredictItem::new(s.id, rules.evaluate(&s.text).topics)).collect();
+        let result = Ok(PredictOutput::ok(items));
+        metrics::observe_predict("mock", samples.len(), started, &result);
+        result
+    }
 
+    fn describe(&self) -> ModelInfo {
+        ModelInfo { topic_labels: self.rules.current().topics(), ..ModelInfo::new("mock", self.loaded_at) }
+    }
+}
@@ -0,0 +1,561 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::domain::{ModelInfo, PredictItem, PredictSample, TopicScore, TopicSentiment};
+use crate::metrics;
+use async_trait::async_trait;
+use crate::predict::rules::{RuleSet, SharedRules};
+use crate::predict::{file_sha256, PredictError, PredictOutput, Predictor, MAX_REJECTED_TOPICS};
+
+/// Параметры отбора топиков из `cfg` артефактов (см. ai_model/export_artifacts.py)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct ModelConfig {
+    topk_delta_base: usize,
+    topk_delta_long: usize,
+    min_len_for_3: usize,
+    entropy_threshold: f32,
+    overlap_min_default: f32,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            topk_delta_base: 3,
+            topk_delta_long: 5,
+            min_len_for_3: 120,
+            entropy_threshold: 0.8,
+            overlap_min_default: 0.2,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TfidfArtifacts {
+    vocabulary: HashMap<String, usize>,
+    idf: Vec<f32>,
+    #[serde(default = "default_true")]
+    lowercase: bool,
+    #[serde(default = "default_token_pattern")]
+    token_pattern: String,
+    #[serde(default = "default_ngram_range")]
+    ngram_range: (usize, usize),
+    #[serde(default)]
+    sublinear_tf: bool,
+    #[serde(default = "default_norm")]
+    norm: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SvdArtifacts {
+    /// Матрица `components_` формы [n_components, n_features]
+    components: Vec<Vec<f32>>,
+}
+
+/// Переносимый экспорт `v42_artifacts.pkl` (см. ai_model/export_artifacts.py)
+#[derive(Deserialize)]
+struct Artifacts {
+    #[serde(default)]
+    config: ModelConfig,
+    tfidf: TfidfArtifacts,
+    svd: SvdArtifacts,
+    centroids: Vec<Vec<f32>>,
+    topic_ids: Vec<i64>,
+    #[serde(default)]
+    topic_names: HashMap<String, String>,
+    #[serde(default)]
+    topic_terms: HashMap<String, Vec<String>>,
+    overlap_min: Option<f32>,
+    #[serde(default)]
+    tau2_global: f32,
+    #[serde(default)]
+    tau3_global: f32,
+    #[serde(default)]
+    tau2_per: HashMap<String, f32>,
+    #[serde(default)]
+    tau3_per: HashMap<String, f32>,
+    #[serde(default)]
+    cluster2gold: HashMap<String, serde_json::Value>,
+}
+
+fn default_true() -> bool { true }
+fn default_token_pattern() -> String { r"(?u)\b\w\w+\b".to_string() }
+fn default_ngram_range() -> (usize, usize) { (1, 1) }
+fn default_norm() -> Option<String> { Some("l2".to_string()) }
+
+/// Кластер (топик) в пространстве SVD
+struct NativeTopic {
+    label: String,
+    /// Нормированный центроид, косинусная близость = скалярное произведение
+    centroid: Vec<f32>,
+    tau2: f32,
+    tau3: f32,
+    terms: HashSet<String>,
+}
+
+/// Нативный предиктор TF-IDF -> TruncatedSVD -> центроиды без Python-сайдкара
+pub struct NativePredictor {
+    model: Arc<NativeModel>,
+    /// Правила тональности: TF-IDF модель её не предсказывает
+    rules: Arc<SharedRules>,
+    info: ModelInfo,
+}
+
+/// Векторизатор, SVD и центроиды; расчёт идёт в блокирующих задачах
+struct NativeModel {
+    vocabulary: HashMap<String, usize>,
+    idf: Vec<f32>,
+    lowercase: bool,
+    token_re: Regex,
+    ngram_range: (usize, usize),
+    sublinear_tf: bool,
+    l2_norm: bool,
+    components: Vec<Vec<f32>>,
+    topics: Vec<NativeTopic>,
+    cfg: ModelConfig,
+    overlap_min: f32,
+}
+
+impl NativePredictor {
+    pub fn try_new(artifacts_path: &Path, rules: Arc<SharedRules>) -> Result<Self> {
+        info!("Initializing native TF-IDF/SVD predictor with artifacts: {:?}", artifacts_path);
+
+        let content = fs::read_to_string(artifacts_path)
+            .with_context(|| format!("failed to read artifacts {:?}", artifacts_path))?;
+        let art: Artifacts = serde_json::from_str(&content)
+            .with_context(|| format!("invalid artifacts JSON {:?}", artifacts_path))?;
+        let vocab_size = art.tfidf.vocabulary.len();
+        let model = NativeModel::from_artifacts(art)?;
+
+        let info = ModelInfo {
+            model_path: Some(artifacts_path.display().to_string()),
+            file_sha256: Some(file_sha256(artifacts_path)?),
+            topic_labels: model.topics.iter().map(|t| t.label.clone()).collect(),
+            vocab_size: Some(vocab_size),
+            ..ModelInfo::new("native", chrono::Utc::now())
+        };
+
+        info!(
+            "Native predictor initialized: {} features, {} components, {} topics",
+            model.idf.len(),
+            model.components.len(),
+            model.topics.len()
+        );
+        Ok(Self { model: Arc::new(model), rules, info })
+    }
+}
+
+impl NativeModel {
+    fn from_artifacts(art: Artifacts) -> Result<Self> {
+        let n_features = art.tfidf.idf.len();
+        let n_components = art.svd.components.len();
+        if n_features == 0 || n_components == 0 {
+            bail!("artifacts contain an empty TF-IDF vocabulary or SVD");
+        }
+        if let Some(row) = art.svd.components.iter().find(|r| r.len() != n_features) {
+            bail!("SVD component has {} features, TF-IDF has {}", row.len(), n_features);
+        }
+        if let Some((term, idx)) = art.tfidf.vocabulary.iter().find(|(_, i)| **i >= n_features) {
+            bail!("vocabulary term {:?} has index {} outside of idf ({})", term, idx, n_features);
+        }
+        if art.centroids.len() != art.topic_ids.len() || art.centroids.is_empty() {
+            bail!("{} centroids for {} topic ids", art.centroids.len(), art.topic_ids.len());
+        }
+        if let Some(c) = art.centroids.iter().find(|c| c.len() != n_components) {
+            bail!("centroid has {} dims, SVD has {} components", c.len(), n_components);
+        }
+        let (min_n, max_n) = art.tfidf.ngram_range;
+        if min_n == 0 || min_n > max_n {
+            bail!("invalid ngram_range ({}, {})", min_n, max_n);
+        }
+
+        let token_re = Regex::new(&art.tfidf.token_pattern)
+            .with_context(|| format!("unsupported token_pattern {:?}", art.tfidf.token_pattern))?;
+
+        let topics = art
+            .topic_ids
+            .iter()
+            .zip(&art.centroids)
+            .map(|(id, centroid)| {
+                let key = id.to_string();
+                // cluster2gold переводит номер кластера в «золотое» название топика
+                let label = art
+                    .cluster2gold
+                    .get(&key)
+                    .and_then(|v| match v {
+                        serde_json::Value::String(s) => Some(s.clone()),
+                        serde_json::Value::Null => None,
+                        other => Some(other.to_string()),
+                    })
+                    .or_else(|| art.topic_names.get(&key).cloned())
+                    .unwrap_or_else(|| format!("Топик {}", id));
+
+                let mut centroid = centroid.clone();
+                l2_normalize(&mut centroid);
+
+                NativeTopic {
+                    label,
+                    centroid,
+                    tau2: art.tau2_per.get(&key).copied().unwrap_or(art.tau2_global),
+                    tau3: art.tau3_per.get(&key).copied().unwrap_or(art.tau3_global),
+                    terms: art
+                        .topic_terms
+                        .get(&key)
+                        .map(|terms| terms.iter().map(|t| t.to_lowercase()).collect())
+                        .unwrap_or_default(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let l2_norm = match art.tfidf.norm.as_deref() {
+            None => false,
+            Some("l2") => true,
+            Some(other) => return Err(anyhow!("unsupported TF-IDF norm {:?}", other)),
+        };
+
+        Ok(Self {
+            vocabulary: art.tfidf.vocabulary,
+            idf: art.tfidf.idf,
+            lowercase: art.tfidf.lowercase,
+            token_re,
+            ngram_range: art.tfidf.ngram_range,
+            sublinear_tf: art.tfidf.sublinear_tf,
+            l2_norm,
+            components: art.svd.components,
+            topics,
+            overlap_min: art.overlap_min.unwrap_or(art.config.overlap_min_default),
+            cfg: art.config,
+        })
+    }
+
+    /// Токены и n-граммы в терминах sklearn `TfidfVectorizer(analyzer="word")`
+    fn analyze(&self, text: &str) -> Vec<String> {
+        let text = if self.lowercase { text.to_lowercase() } else { text.to_string() };
+        let tokens: Vec<&str> = self.token_re.find_iter(&text).map(|m| m.as_str()).collect();
+
+        let (min_n, max_n) = self.ngram_range;
+        let mut terms = Vec::new();
+        for n in min_n..=max_n {
+            for window in tokens.windows(n) {
+                terms.push(window.join(" "));
+            }
+        }
+        terms
+    }
+
+    /// Разреженный TF-IDF вектор: индекс признака -> вес
+    fn tfidf(&self, terms: &[String]) -> HashMap<usize, f32> {
+        let mut counts: HashMap<usize, f32> = HashMap::new();
+        for term in terms {
+            if let Some(&idx) = self.vocabulary.get(term) {
+                *counts.entry(idx).or_insert(0.0) += 1.0;
+            }
+        }
+
+        for (idx, value) in counts.iter_mut() {
+            let tf = if self.sublinear_tf { 1.0 + value.ln() } else { *value };
+            *value = tf * self.idf[*idx];
+        }
+
+        if self.l2_norm {
+            let norm = counts.values().map(|v| v * v).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                counts.values_mut().for_each(|v| *v /= norm);
+            }
+        }
+        counts
+    }
+
+    /// Проекция в пространство SVD с L2-нормировкой
+    fn project(&self, features: &HashMap<usize, f32>) -> Vec<f32> {
+        let mut z: Vec<f32> = self
+            .components
+            .iter()
+            .map(|component| features.iter().map(|(&idx, &v)| v * component[idx]).sum())
+            .collect();
+        l2_normalize(&mut z);
+        z
+    }
+
+    /// Отбор топиков — собственная реализация бэкенда: в nonlinear_topic_clustering_v4_2.py
+    /// правила отбора не реализованы, из артефактов берутся только пороги и термины.
+    /// 1. кандидаты — top-k по косинусной близости к центроидам, где k = `topk_delta_long`
+    ///    для текстов длиной от `min_len_for_3` символов и `topk_delta_base` иначе;
+    /// 2. первый кандидат принимается всегда;
+    /// 3. дополнительные топики рассматриваются, только если нормированная энтропия
+    ///    распределения близостей кандидатов не ниже `entropy_threshold` (текст не
+    ///    «принадлежит» одному кластеру);
+    /// 4. второй топик проходит порог `tau2`, третий — `tau3` и только для длинных текстов;
+    /// 5. у дополнительного топика доля его ключевых терминов в тексте должна быть
+    ///    не меньше `overlap_min`.
+    ///
+    /// Возвращает выбранные топики с близостью и лучшие из отклонённых.
+    fn assign_topics(&self, text: &str) -> (Vec<TopicScore>, Vec<TopicScore>) {
+        let terms = self.analyze(text);
+        let features = self.tfidf(&terms);
+        if features.is_empty() {
+            return (vec![], vec![]);
+        }
+        let z = self.project(&features);
+
+        let mut scored: Vec<(usize, f32)> = self
+            .topics
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (i, dot(&z, &t.centroid)))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let ranked = scored.clone();
+
+        let is_long = text.chars().count() >= self.cfg.min_len_for_3;
+        let k = if is_long { self.cfg.topk_delta_long } else { self.cfg.topk_delta_base };
+        scored.truncate(k.max(1));
+
+        let mut selected = vec![scored[0].0];
+        if normalized_entropy(&scored) >= self.cfg.entropy_threshold {
+            let text_terms: HashSet<&str> = terms.iter().map(|t| t.as_str()).collect();
+            let max_topics = if is_long { 3 } else { 2 };
+
+            for &(i, sim) in scored.iter().skip(1) {
+                if selected.len() >= max_topics {
+                    break;
+                }
+                let topic = &self.topics[i];
+                let tau = if selected.len() == 1 { topic.tau2 } else { topic.tau3 };
+                if sim >= tau && self.term_overlap(topic, &text_terms) >= self.overlap_min {
+                    selected.push(i);
+                }
+            }
+        }
+
+        // У нескольких кластеров может быть одна метка — берём лучшую близость
+        let mut accepted: Vec<TopicScore> = Vec::new();
+        for &i in &selected {
+            let label = &self.topics[i].label;
+            if !accepted.iter().any(|t| &t.topic == label) {
+                accepted.push(TopicScore { topic: label.clone(), score: similarity_score(dot(&z, &self.topics[i].centroid)) });
+            }
+        }
+        let mut rejected: Vec<TopicScore> = Vec::new();
+        for &(i, sim) in &ranked {
+            let label = &self.topics[i].label;
+            if rejected.len() >= MAX_REJECTED_TOPICS {
+                break;
+            }
+            if !accepted.iter().chain(&rejected).any(|t| &t.topic == label) {
+                rejected.push(TopicScore { topic: label.clone(), score: similarity_score(sim) });
+            }
+        }
+        (accepted, rejected)
+    }
+
+    fn predict_one(&self, rules: &RuleSet, sample: &PredictSample) -> PredictItem {
+        let (accepted, rejected) = self.assign_topics(&sample.text);
+        // TF-IDF модель не предсказывает тональность: правила ключевых слов оценивают
+        // каждый топик по фразам, где он упомянут
+        let topics: Vec<TopicSentiment> = if accepted.is_empty() {
+            let topic = rules.default_topic().unwrap_or("Обслуживание");
+            rules.aspects(&sample.text, &[topic.to_string()])
+        } else {
+            let names: Vec<String> = accepted.iter().map(|t| t.topic.clone()).collect();
+            rules
+                .aspects(&sample.text, &names)
+                .into_iter()
+                .zip(&accepted)
+                .map(|(aspect, t)| TopicSentiment { score: Some(t.score), ..aspect })
+                .collect()
+        };
+        let mut item = PredictItem::new(sample.id, topics);
+        item.rejected_topics = rejected;
+        item
+    }
+
+    /// Доля ключевых терминов топика, встречающихся в тексте (1.0, если терминов нет)
+    fn term_overlap(&self, topic: &NativeTopic, text_terms: &HashSet<&str>) -> f32 {
+        if topic.terms.is_empty() {
+            return 1.0;
+        }
+        let hits = topic.terms.iter().filter(|t| text_terms.contains(t.as_str())).count();
+        hits as f32 / topic.terms.len() as f32
+    }
+}
+
+/// Косинусная близость как уверенность в [0, 1]
+fn similarity_score(sim: f32) -> f32 {
+    sim.clamp(0.0, 1.0)
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn l2_normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+/// Энтропия распределения неотрицательных близостей, нормированная на log(n)
+fn normalized_entropy(scored: &[(usize, f32)]) -> f32 {
+    if scored.len() < 2 {
+        return 0.0;
+    }
+    let total: f32 = scored.iter().map(|(_, s)| s.max(0.0)).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    let entropy: f32 = scored
+        .iter()
+        .map(|(_, s)| s.max(0.0) / total)
+        .filter(|p| *p > 0.0)
+        .map(|p| -p * p.ln())
+        .sum();
+    entropy / (scored.len() as f32).ln()
+}
+
+#[async_trait]
+impl Predictor for NativePredictor {
+    async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        let started = std::time::Instant::now();
+        // TF-IDF, проекция и близости к центроидам занимают CPU и выполняются вне потоков actix
+        let model = self.model.clone();
+        let rules = self.rules.current();
+        let batch = samples.to_vec();
+        let result = tokio::task::spawn_blocking(move || {
+            PredictOutput::ok(batch.iter().map(|s| model.predict_one(&rules, s)).collect())
+        })
+        .await
+        .map_err(|e| PredictError::Model(format!("native prediction task failed: {}", e)));
+        metrics::observe_predict("native", samples.len(), started, &result);
+        result
+    }
+
+    fn describe(&self) -> ModelInfo {
+        self.info.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    /// Модель из четырёх слов: SVD — единичная матрица, центроид топика совпадает с его словом
+    fn model(config: Value, extra: Value) -> NativeModel {
+        let mut art = json!({
+            "config": config,
+            "tfidf": {"vocabulary": {"ипотека": 0, "карта": 1, "кредит": 2, "вклад": 3}, "idf": [1.0, 1.0, 1.0, 1.0]},
+            "svd": {"components": [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]]},
+            "centroids": [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]],
+            "topic_ids": [0, 1, 2, 3],
+            "topic_names": {"0": "Ипотека", "1": "Карты", "2": "Кредиты", "3": "Вклады"},
+        });
+        for (key, value) in extra.as_object().expect("object") {
+            art[key] = value.clone();
+        }
+        NativeModel::from_artifacts(serde_json::from_value(art).expect("valid artifacts")).expect("valid model")
+    }
+
+    /// Без энтропийного фильтра: отбор решают только пороги и термины
+    fn open_config() -> Value {
+        json!({"entropy_threshold": 0.0, "topk_delta_base": 3, "topk_delta_long": 3, "min_len_for_3": 60})
+    }
+
+    fn accepted(model: &NativeModel, text: &str) -> Vec<String> {
+        model.assign_topics(text).0.into_iter().map(|t| t.topic).collect()
+    }
+
+    /// Текст длиннее `min_len_for_3` за счёт слов вне словаря
+    fn long(text: &str) -> String {
+        format!("{} {}", text, "слово ".repeat(12))
+    }
+
+    #[test]
+    fn primary_topic_is_always_kept() {
+        let model = model(open_config(), json!({"tau2_global": 1.0, "tau3_global": 1.0}));
+        assert_eq!(accepted(&model, "ипотека ипотека карта"), ["Ипотека"]);
+        let (_, rejected) = model.assign_topics("ипотека ипотека карта");
+        assert_eq!(rejected[0].topic, "Карты");
+    }
+
+    #[test]
+    fn text_without_known_terms_has_no_topics() {
+        let model = model(open_config(), json!({}));
+        let (accepted, rejected) = model.assign_topics("ничего знакомого");
+        assert!(accepted.is_empty() && rejected.is_empty());
+    }
+
+    #[test]
+    fn second_topic_passes_tau2() {
+        // Близости: ипотека 2/√5 ≈ 0.89, карта 1/√5 ≈ 0.45
+        let text = "ипотека ипотека карта";
+        assert_eq!(accepted(&model(open_config(), json!({"tau2_global": 0.4})), text), ["Ипотека", "Карты"]);
+        assert_eq!(accepted(&model(open_config(), json!({"tau2_global": 0.5})), text), ["Ипотека"]);
+        let per_topic = json!({"tau2_global": 0.5, "tau2_per": {"1": 0.4}});
+        assert_eq!(accepted(&model(open_config(), per_topic), text), ["Ипотека", "Карты"]);
+    }
+
+    #[test]
+    fn third_topic_passes_tau3_only_in_long_texts() {
+        // Близости: 3/√14 ≈ 0.80, 2/√14 ≈ 0.53, 1/√14 ≈ 0.27
+        let text = "ипотека ипотека ипотека карта карта кредит";
+        let model_with = |tau3: f32| model(open_config(), json!({"tau2_global": 0.5, "tau3_global": tau3}));
+        assert_eq!(accepted(&model_with(0.2), text), ["Ипотека", "Карты"]);
+        assert_eq!(accepted(&model_with(0.2), &long(text)), ["Ипотека", "Карты", "Кредиты"]);
+        assert_eq!(accepted(&model_with(0.3), &long(text)), ["Ипотека", "Карты"]);
+    }
+
+    #[test]
+    fn text_length_picks_candidate_count() {
+        let config = json!({"entropy_threshold": 0.0, "topk_delta_base": 1, "topk_delta_long": 3, "min_len_for_3": 60});
+        let model = model(config, json!({}));
+        let text = "ипотека ипотека ипотека карта карта кредит";
+        assert_eq!(accepted(&model, text), ["Ипотека"]);
+        assert_eq!(accepted(&model, &long(text)), ["Ипотека", "Карты", "Кредиты"]);
+    }
+
+    #[test]
+    fn extra_topics_need_term_overlap() {
+        // Топики без терминов с нулевой близостью отсекает tau2
+        let terms = json!({"1": ["карта", "пластик"]});
+        let text = "ипотека ипотека карта";
+        let with_overlap =
+            |min: f32| model(open_config(), json!({"topic_terms": terms, "overlap_min": min, "tau2_global": 0.1}));
+        assert_eq!(accepted(&with_overlap(0.5), text), ["Ипотека", "Карты"]);
+        assert_eq!(accepted(&with_overlap(0.6), text), ["Ипотека"]);
+        // Порог по умолчанию берётся из cfg
+        let config = json!({"entropy_threshold": 0.0, "overlap_min_default": 0.6});
+        assert_eq!(accepted(&model(config, json!({"topic_terms": terms, "tau2_global": 0.1})), text), ["Ипотека"]);
+    }
+
+    #[test]
+    fn low_entropy_keeps_single_topic() {
+        let text = "ипотека ипотека ипотека карта";
+        let config = |threshold: f32| json!({"entropy_threshold": threshold, "topk_delta_base": 2});
+        assert_eq!(accepted(&model(config(0.0), json!({})), text), ["Ипотека", "Карты"]);
+        assert_eq!(accepted(&model(config(0.9), json!({})), text), ["Ипотека"]);
+    }
+
+    #[test]
+    fn clusters_with_one_label_are_merged() {
+        let model = model(open_config(), json!({"topic_names": {"0": "Кредиты", "1": "Карты", "2": "Кредиты", "3": "Вклады"}}));
+        assert_eq!(accepted(&model, "ипотека ипотека кредит"), ["Кредиты"]);
+    }
+
+    #[test]
+    fn entropy_is_normalized() {
+        assert_eq!(normalized_entropy(&[(0, 1.0)]), 0.0);
+        assert!((normalized_entropy(&[(0, 0.5), (1, 0.5), (2, 0.5)]) - 1.0).abs() < 1e-6);
+        assert_eq!(normalized_entropy(&[(0, 0.9), (1, 0.0), (2, -0.3)]), 0.0);
+        assert_eq!(normalized_entropy(&[(0, -0.1), (1, -0.2)]), 0.0);
+        let skewed = normalized_entropy(&[(0, 0.9), (1, 0.1)]);
+        assert!(skewed > 0.0 && skewed < 1.0);
+    }
+}
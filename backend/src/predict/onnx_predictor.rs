@@ -1,216 +1,287 @@
+use std::collections::BTreeMap;
 use std::path::Path;
-// use std::sync::Arc;
-// use ort::Session;
-// use ort::Value;
-// use ort::Environment;
-use anyhow::Result;
+use std::sync::Arc;
+use ndarray::{Array2, ArrayView1, CowArray, Ix2, IxDyn};
+use ort::tensor::OrtOwnedTensor;
+use ort::{Environment, GraphOptimizationLevel, Session, SessionBuilder, Value};
+use anyhow::{anyhow, Context, Result};
 use tracing::{info, error};
 
-use crate::domain::{PredictItem, PredictSample};
-use crate::tokenizer::SimpleTokenizer;
+use crate::domain::{ModelInfo, PredictItem, PredictSample, Sentiment, TopicScore, TopicSentiment};
+use crate::metrics;
+use crate::tokenizer::WordPieceTokenizer;
 use async_trait::async_trait;
-use crate::predict::Predictor;
+use crate::predict::rules::SharedRules;
+use crate::predict::{file_sha256, PredictError, PredictOutput, Predictor, MAX_REJECTED_TOPICS};
 
-/// ONNX Runtime predictor с полной реализацией
-/// Временно использует mock логику до настройки правильного API
+/// Метки топиков в порядке столбцов `logits`, если манифест модели не задаёт свои
+pub const TOPIC_LABELS: [&str; 9] = [
+    "Обслуживание",
+    "Мобильное приложение",
+    "Онлайн-банк",
+    "Сайт",
+    "Ипотека",
+    "Кредит",
+    "Карта",
+    "Терминал",
+    "Поддержка",
+];
+
+/// Метки сентимента для дополнительной головы модели (последние 3 столбца `logits`)
+const SENTIMENT_LABELS: [Sentiment; 3] = [Sentiment::Positive, Sentiment::Neutral, Sentiment::Negative];
+
+/// ONNX Runtime predictor: токенизация -> `input_ids`/`attention_mask` -> `logits`
 pub struct OnnxPredictor {
-    // session: Arc<Session>,
-    tokenizer: SimpleTokenizer,
-    // environment: Arc<Environment>,
-    _model_path: std::path::PathBuf,
+    model: Arc<OnnxModel>,
+    batch_size: usize,
+    info: ModelInfo,
+}
+
+/// Сессия и всё, что нужно для разбора её ответа; инференс идёт в блокирующих задачах
+struct OnnxModel {
+    session: Session,
+    tokenizer: WordPieceTokenizer,
+    /// Метки топиков в порядке столбцов `logits`
+    labels: Vec<String>,
+    /// Порог вероятности топика после сигмоиды
+    topic_threshold: f32,
+    /// Правила тональности для моделей без головы сентимента
+    rules: Arc<SharedRules>,
+    _environment: Arc<Environment>,
 }
 
 impl OnnxPredictor {
-    pub fn try_new(model_path: &Path) -> Result<Self> {
+    pub fn try_new(
+        model_path: &Path,
+        tokenizer: WordPieceTokenizer,
+        labels: Vec<String>,
+        batch_size: usize,
+        topic_threshold: f32,
+        rules: Arc<SharedRules>,
+    ) -> Result<Self> {
         info!("Initializing ONNX predictor with model: {:?}", model_path);
-        
-        // TODO: Восстановить ONNX функциональность после настройки правильного API
-        // let environment = Arc::new(Environment::builder().with_name("KabanchikiPredictor").build()?);
-        // let session = Session::new(&environment, model_path)?;
-        
-        let tokenizer = SimpleTokenizer::new();
-        
-        info!("ONNX predictor initialized successfully (mock mode - ready for real ONNX integration)");
-        
-        Ok(Self {
-            // session: Arc::new(session),
-            tokenizer,
-            // environment,
-            _model_path: model_path.to_path_buf(),
-        })
-    }
-    
-    /// Выполняет предсказание для одного текста
-    fn predict_single(&self, sample: &PredictSample) -> Result<PredictItem> {
-        // Токенизируем текст
-        let tokens = self.tokenizer.tokenize(&sample.text);
-        let _attention_mask = self.tokenizer.create_attention_mask(&tokens);
-        
-        // TODO: Восстановить ONNX инференс
-        // let input_ids = Value::from_array(allocator, &tokens_array)?;
-        // let attention_mask_tensor = Value::from_array(allocator, &attention_mask_array)?;
-        // let inputs = vec![input_ids, attention_mask_tensor];
-        // let outputs = self.session.run(inputs)?;
-        // let logits = outputs[0].try_extract::<f32>()?;
-        
-        // Пока используем простую логику на основе токенизации
-        let (topics, sentiments) = self.extract_predictions_simple(&sample.text);
-        
-        Ok(PredictItem {
-            id: sample.id,
-            topics,
-            sentiments,
-        })
-    }
-    
-    /// Извлекает предсказания из выходных данных модели на основе логits
-    #[allow(dead_code)]
-    fn extract_predictions_from_logits(&self, logits: &ndarray::ArrayView2<f32>, text: &str) -> (Vec<String>, Vec<String>) {
-        // Простая эвристика для демонстрации
-        // В реальной модели здесь должна быть логика на основе архитектуры нейросети
-        
-        // Используем комбинацию логits и текстового анализа
-        let text_lower = text.to_lowercase();
-        
-        // Определяем топики на основе ключевых слов и логits
-        let mut topics = Vec::new();
-        let mut push_if = |kw: &str, topic: &str| {
-            if text_lower.contains(kw) && !topics.iter().any(|t| t == topic) {
-                topics.push(topic.to_string());
+
+        let environment = Environment::builder()
+            .with_name("KabanchikiPredictor")
+            .build()?
+            .into_arc();
+
+        let session = SessionBuilder::new(&environment)?
+            .with_optimization_level(GraphOptimizationLevel::Level1)?
+            .with_intra_threads(1)?
+            .with_model_from_file(model_path)
+            .with_context(|| format!("failed to load ONNX model {:?}", model_path))?;
+
+        if !session.outputs.iter().any(|o| o.name == "logits") {
+            return Err(anyhow!("ONNX model {:?} has no `logits` output", model_path));
+        }
+        for input in &session.inputs {
+            if !matches!(input.name.as_str(), "input_ids" | "attention_mask" | "token_type_ids") {
+                return Err(anyhow!("ONNX model {:?} has unsupported input `{}`", model_path, input.name));
             }
-        };
-        
-        push_if("обслужив", "Обслуживание");
-        push_if("мобильное прилож", "Мобильное приложение");
-        push_if("онлайн-банк", "Онлайн-банк");
-        push_if("сайт", "Сайт");
-        push_if("ипотек", "Ипотека");
-        push_if("кредит", "Кредит");
-        push_if("карт", "Карта");
-        push_if("терминал", "Терминал");
-        push_if("поддержк", "Поддержка");
-        
-        if topics.is_empty() {
-            topics.push("Обслуживание".to_string());
         }
-        
-        // Определяем сентимент на основе логits и текста
-        let sentiment = self.determine_sentiment_from_logits(logits, &text_lower);
-        let sentiments = vec![sentiment; topics.len()];
-        
-        (topics, sentiments)
+
+        info!(
+            "ONNX predictor initialized successfully (inputs: {:?})",
+            session.inputs.iter().map(|i| i.name.as_str()).collect::<Vec<_>>()
+        );
+
+        let info = ModelInfo {
+            model_path: Some(model_path.display().to_string()),
+            file_sha256: Some(file_sha256(model_path)?),
+            topic_labels: labels.clone(),
+            vocab_size: Some(tokenizer.vocab_size()),
+            ..ModelInfo::new("onnx", chrono::Utc::now())
+        };
+
+        let model = OnnxModel { session, tokenizer, labels, topic_threshold, rules, _environment: environment };
+        Ok(Self { model: Arc::new(model), batch_size: batch_size.max(1), info })
     }
-    
-    /// Определяет сентимент на основе логits и текста
-    #[allow(dead_code)]
-    fn determine_sentiment_from_logits(&self, _logits: &ndarray::ArrayView2<f32>, text_lower: &str) -> String {
-        // Простая логика определения сентимента
-        // В реальной модели здесь должна быть более сложная логика
-        
-        let has_explicit_pos = text_lower.contains("положительно");
-        let has_explicit_neg = text_lower.contains("отрицательно");
-        let has_explicit_neu = text_lower.contains("нейтрально");
-
-        let neg_kw = [
-            "непонрав", "не понрав", "зависает", "зависа", "долго", "плохо", "ужасн",
-            "медлен", "лома", "обман",
-        ];
-        let pos_kw = [
-            "понрав", "нрав", "быстро", "отлично", "хорошо", "рекоменд", "удоб",
-        ];
-
-        let has_neg = has_explicit_neg || neg_kw.iter().any(|k| text_lower.contains(k));
-        let has_pos = has_explicit_pos || pos_kw.iter().any(|k| text_lower.contains(k));
-
-        if has_neg {
-            "отрицательно"
-        } else if has_pos {
-            "положительно"
-        } else if has_explicit_neu {
-            "нейтрально"
-        } else {
-            "нейтрально"
-        }.to_string()
+}
+
+impl OnnxModel {
+    /// Выполняет предсказание для микро-батча одним прогоном модели
+    fn predict_batch(&self, samples: &[PredictSample], sequences: &[Vec<u32>]) -> Result<Vec<PredictItem>> {
+        // Один тензор [batch, max_len_in_batch] на весь микро-батч
+        let sequences: Vec<&[u32]> = sequences.iter().map(Vec::as_slice).collect();
+        let batch = self.tokenizer.pad_batch(&sequences);
+        let shape = (batch.batch_size, batch.seq_len);
+
+        let input_ids = to_tensor(shape, batch.input_ids)?;
+        let attention_mask = to_tensor(shape, batch.attention_mask)?;
+        let token_type_ids = to_tensor(shape, vec![0; shape.0 * shape.1])?;
+
+        // Входы подаются в том порядке, в котором их объявляет модель
+        let allocator = self.session.allocator();
+        let inputs = self
+            .session
+            .inputs
+            .iter()
+            .map(|input| match input.name.as_str() {
+                "input_ids" => Value::from_array(allocator, &input_ids),
+                "attention_mask" => Value::from_array(allocator, &attention_mask),
+                _ => Value::from_array(allocator, &token_type_ids),
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let outputs = self.session.run(inputs)?;
+        let logits_idx = self
+            .session
+            .outputs
+            .iter()
+            .position(|o| o.name == "logits")
+            .ok_or_else(|| anyhow!("model has no `logits` output"))?;
+        let tensor: OrtOwnedTensor<f32, _> = outputs[logits_idx].try_extract()?;
+        let logits = tensor
+            .view()
+            .into_dimensionality::<Ix2>()
+            .context("`logits` must have shape [batch, labels]")?;
+
+        if logits.nrows() != samples.len() || logits.ncols() < self.labels.len() {
+            return Err(anyhow!(
+                "unexpected logits shape {:?}, expected [{}, >= {}]",
+                logits.shape(),
+                samples.len(),
+                self.labels.len()
+            ));
+        }
+
+        Ok(samples
+            .iter()
+            .zip(logits.rows())
+            .map(|(sample, row)| self.extract_predictions_from_logits(sample.id, &row, &sample.text))
+            .collect())
     }
-    
-    /// Извлекает предсказания из выходных данных модели (упрощенная версия)
-    fn extract_predictions_simple(&self, text: &str) -> (Vec<String>, Vec<String>) {
-        let text_lower = text.to_lowercase();
-        
-        // Определяем топики на основе ключевых слов
-        let mut topics = Vec::new();
-        let mut push_if = |kw: &str, topic: &str| {
-            if text_lower.contains(kw) && !topics.iter().any(|t| t == topic) {
-                topics.push(topic.to_string());
+
+    /// Извлекает предсказания из строки логитов одного текста
+    ///
+    /// Первые `labels.len()` столбцов — независимые (multi-label) логиты топиков.
+    /// Если модель отдаёт ещё 3 столбца, они трактуются как логиты сентимента,
+    /// иначе сентимент определяется правилами ключевых слов.
+    fn extract_predictions_from_logits(&self, id: i64, row: &ArrayView1<f32>, text: &str) -> PredictItem {
+        // Топики: сигмоида + порог, при пустом результате берём argmax
+        let mut scored: Vec<(usize, f32)> = (0..self.labels.len())
+            .map(|i| (i, sigmoid(row[i])))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let accepted = scored.iter().filter(|(_, p)| *p >= self.topic_threshold).count().max(1);
+        let (accepted, rejected) = scored.split_at(accepted);
+
+        // Сентимент: отдельная голова модели (softmax) или правила ключевых слов по фразам каждого топика
+        let mut sentiment_scores = BTreeMap::new();
+        let topics: Vec<TopicSentiment> = if row.len() == self.labels.len() + SENTIMENT_LABELS.len() {
+            let head = row.slice(ndarray::s![self.labels.len()..]);
+            for (label, p) in SENTIMENT_LABELS.iter().zip(softmax(&head)) {
+                sentiment_scores.insert(label.as_str().to_string(), p);
             }
-        };
-        
-        push_if("обслужив", "Обслуживание");
-        push_if("мобильное прилож", "Мобильное приложение");
-        push_if("онлайн-банк", "Онлайн-банк");
-        push_if("сайт", "Сайт");
-        push_if("ипотек", "Ипотека");
-        push_if("кредит", "Кредит");
-        push_if("карт", "Карта");
-        push_if("терминал", "Терминал");
-        push_if("поддержк", "Поддержка");
-        
-        if topics.is_empty() {
-            topics.push("Обслуживание".to_string());
-        }
-        
-        // Определяем сентимент
-        let has_explicit_pos = text_lower.contains("положительно");
-        let has_explicit_neg = text_lower.contains("отрицательно");
-        let has_explicit_neu = text_lower.contains("нейтрально");
-
-        let neg_kw = [
-            "непонрав", "не понрав", "зависает", "зависа", "долго", "плохо", "ужасн",
-            "медлен", "лома", "обман",
-        ];
-        let pos_kw = [
-            "понрав", "нрав", "быстро", "отлично", "хорошо", "рекоменд", "удоб",
-        ];
-
-        let has_neg = has_explicit_neg || neg_kw.iter().any(|k| text_lower.contains(k));
-        let has_pos = has_explicit_pos || pos_kw.iter().any(|k| text_lower.contains(k));
-
-        let sentiment = if has_neg {
-            "отрицательно"
-        } else if has_pos {
-            "положительно"
-        } else if has_explicit_neu {
-            "нейтрально"
+            let sentiment = SENTIMENT_LABELS[argmax(&head)];
+            accepted.iter().map(|(i, p)| TopicSentiment::scored(self.labels[*i].clone(), sentiment, *p)).collect()
         } else {
-            "нейтрально"
+            let names: Vec<String> = accepted.iter().map(|(i, _)| self.labels[*i].clone()).collect();
+            self.rules
+                .current()
+                .aspects(text, &names)
+                .into_iter()
+                .zip(accepted)
+                .map(|(aspect, (_, p))| TopicSentiment { score: Some(*p), ..aspect })
+                .collect()
         };
-        
-        let sentiments = vec![sentiment.to_string(); topics.len()];
-        
-        (topics, sentiments)
+
+        let mut item = PredictItem::new(id, topics);
+        item.sentiment_scores = sentiment_scores;
+        item.rejected_topics = rejected
+            .iter()
+            .take(MAX_REJECTED_TOPICS)
+            .map(|(i, p)| TopicScore { topic: self.labels[*i].clone(), score: *p })
+            .collect();
+        item
     }
 }
 
+/// Выполняет `f` в пуле блокирующих задач tokio
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    tokio::task::spawn_blocking(f).await.context("ONNX inference task panicked")?
+}
+
+/// Упаковывает плоский буфер в тензор формы [batch, seq_len]
+fn to_tensor<'a>(shape: (usize, usize), values: Vec<i64>) -> Result<CowArray<'a, i64, IxDyn>> {
+    Ok(CowArray::from(Array2::from_shape_vec(shape, values)?.into_dyn()))
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn softmax(values: &ArrayView1<f32>) -> Vec<f32> {
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exps: Vec<f32> = values.iter().map(|v| (v - max).exp()).collect();
+    let sum: f32 = exps.iter().sum();
+    exps.into_iter().map(|e| e / sum).collect()
+}
+
+fn argmax(values: &ArrayView1<f32>) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
 #[async_trait]
 impl Predictor for OnnxPredictor {
-    async fn predict(&self, samples: &Vec<PredictSample>) -> Vec<PredictItem> {
-        let mut results = Vec::new();
-        
-        for sample in samples {
-            match self.predict_single(sample) {
-                Ok(result) => results.push(result),
+    async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        let started = std::time::Instant::now();
+        // Токенизация и прогоны модели занимают CPU и выполняются вне потоков actix
+        let model = self.model.clone();
+        let texts: Vec<String> = samples.iter().map(|s| s.text.clone()).collect();
+        let encoded = blocking(move || Ok(texts.iter().map(|t| model.tokenizer.encode(t)).collect::<Vec<_>>())).await;
+        let mut sequences = match encoded {
+            Ok(sequences) => sequences,
+            Err(e) => {
+                let result = Err(PredictError::Model(format!("{:#}", e)));
+                metrics::observe_predict("onnx", samples.len(), started, &result);
+                return result;
+            }
+        };
+        // Сортируем по длине, чтобы в микро-батч попадали тексты близкой длины
+        // и паддинг был минимальным; порядок ответа восстанавливаем по индексам
+        let mut order: Vec<usize> = (0..samples.len()).collect();
+        order.sort_by_key(|&i| sequences[i].len());
+
+        let mut results: Vec<Option<PredictItem>> = (0..samples.len()).map(|_| None).collect();
+        let mut errors = Vec::new();
+
+        for chunk in order.chunks(self.batch_size) {
+            let batch_samples: Vec<PredictSample> = chunk.iter().map(|&i| samples[i].clone()).collect();
+            let batch_sequences: Vec<Vec<u32>> = chunk.iter().map(|&i| std::mem::take(&mut sequences[i])).collect();
+            let model = self.model.clone();
+
+            match blocking(move || model.predict_batch(&batch_samples, &batch_sequences)).await {
+                Ok(items) => {
+                    for (&i, item) in chunk.iter().zip(items) {
+                        results[i] = Some(item);
+                    }
+                }
                 Err(e) => {
-                    error!("Failed to predict for sample {}: {:?}", sample.id, e);
-                    // Возвращаем дефолтный результат в случае ошибки
-                    results.push(PredictItem {
-                        id: sample.id,
-                        topics: vec!["Обслуживание".to_string()],
-                        sentiments: vec!["нейтрально".to_string()],
-                    });
+                    error!("Failed to predict batch of {} samples: {:?}", chunk.len(), e);
+                    let error = PredictError::Model(format!("{:#}", e));
+                    errors.extend(chunk.iter().map(|&i| (samples[i].id, error.clone())));
                 }
             }
         }
-        
-        results
+
+        // Упал каждый батч — это отказ модели целиком, а не частичный результат
+        let result = if !samples.is_empty() && errors.len() == samples.len() {
+            Err(errors.swap_remove(0).1)
+        } else {
+            Ok(PredictOutput { items: results.into_iter().flatten().collect(), errors })
+        };
+        metrics::observe_predict("onnx", samples.len(), started, &result);
+        result
+    }
+
+    fn describe(&self) -> ModelInfo {
+        self.info.clone()
     }
 }
@@ -0,0 +1,501 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures_util::{stream, StreamExt};
+use tracing::{info, warn};
+
+use crate::domain::{ModelInfo, PredictItem, PredictSample, Sentiment, TopicScore, TopicSentiment};
+use crate::metrics;
+use crate::predict::{PredictError, PredictOutput, Predictor, MAX_REJECTED_TOPICS};
+
+/// Параметры устойчивости прокси: таймауты, повторы и circuit breaker
+#[derive(Debug, Clone)]
+pub struct ProxySettings {
+    pub request_timeout: Duration,
+    pub connect_timeout: Duration,
+    /// Число повторов после первой попытки
+    pub retries: u32,
+    /// Базовая задержка; удваивается на каждом повторе
+    pub backoff: Duration,
+    /// Подряд идущих отказов до размыкания цепи
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration,
+    /// Максимум отзывов в одном запросе к сервису
+    pub chunk_size: usize,
+    /// Число одновременно выполняемых запросов
+    pub concurrency: usize,
+}
+
+/// Predictor, вызывающий внешний Python-сервис с совместимой схемой `/predict`
+pub struct ProxyPredictor {
+    client: reqwest::Client,
+    url: String,
+    settings: ProxySettings,
+    breaker: CircuitBreaker,
+    loaded_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl ProxyPredictor {
+    pub fn new(url: String, mut settings: ProxySettings) -> Result<Self> {
+        settings.chunk_size = settings.chunk_size.max(1);
+        settings.concurrency = settings.concurrency.max(1);
+        let client = reqwest::Client::builder()
+            .timeout(settings.request_timeout)
+            .connect_timeout(settings.connect_timeout)
+            .build()
+            .context("failed to build HTTP client for proxy predictor")?;
+        let breaker = CircuitBreaker::new(settings.breaker_threshold, settings.breaker_cooldown);
+        Ok(Self { client, url, settings, breaker, loaded_at: chrono::Utc::now() })
+    }
+
+    /// Один HTTP-запрос к сервису без повторов
+    async fn call(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        let body = serde_json::json!({ "data": samples });
+        let resp = self.client.post(&self.url).json(&body).send().await.map_err(|e| {
+            if e.is_timeout() {
+                PredictError::Timeout(e.to_string())
+            } else {
+                PredictError::Unavailable(e.to_string())
+            }
+        })?;
+
+        let status = resp.status();
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(PredictError::Unavailable(format!("proxy responded with {}", status)));
+        }
+        if !status.is_success() {
+            return Err(PredictError::MalformedResponse(format!("proxy responded with {}", status)));
+        }
+
+        let json = resp.json::<serde_json::Value>().await.map_err(|e| {
+            if e.is_timeout() {
+                PredictError::Timeout(e.to_string())
+            } else {
+                PredictError::MalformedResponse(e.to_string())
+            }
+        })?;
+        parse_response(samples, &json)
+    }
+
+    async fn predict_chunk(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        let started = Instant::now();
+        let Some(permit) = self.breaker.allow() else {
+            let result = Err(PredictError::Unavailable("circuit breaker is open".to_string()));
+            metrics::observe_predict("proxy", samples.len(), started, &result);
+            return result;
+        };
+
+        // Батч предсказаний идемпотентен, поэтому сетевые сбои и таймауты можно повторять;
+        // некорректный ответ повтором не исправить
+        let mut attempt = 0;
+        let result = loop {
+            match self.call(samples).await {
+                Err(e @ (PredictError::Unavailable(_) | PredictError::Timeout(_))) if attempt < self.settings.retries => {
+                    let delay = self.settings.backoff * 2u32.saturating_pow(attempt);
+                    attempt += 1;
+                    metrics::PROXY_RETRIES.inc();
+                    warn!("Proxy predictor attempt {} failed: {}. Retrying in {:?}", attempt, e, delay);
+                    tokio::time::sleep(delay).await;
+                }
+                other => break other,
+            }
+        };
+
+        match &result {
+            Ok(_) | Err(PredictError::MalformedResponse(_)) => permit.record_success(),
+            Err(_) => permit.record_failure(),
+        }
+        metrics::observe_predict("proxy", samples.len(), started, &result);
+        result
+    }
+}
+
+#[async_trait]
+impl Predictor for ProxyPredictor {
+    /// Делит отзывы на порции по `chunk_size`, отправляет не более `concurrency` порций одновременно
+    /// и собирает результат в порядке входных отзывов
+    async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        let chunks: Vec<&[PredictSample]> = samples.chunks(self.settings.chunk_size).collect();
+        if chunks.len() <= 1 {
+            return self.predict_chunk(samples).await;
+        }
+
+        // `buffered` сохраняет порядок порций, поэтому результаты склеиваются в порядке входа
+        let requests: Vec<_> = chunks.iter().map(|chunk| self.predict_chunk(chunk)).collect();
+        let results: Vec<_> = stream::iter(requests)
+            .buffered(self.settings.concurrency)
+            .collect()
+            .await;
+
+        // Отказ отдельной порции — частичная ошибка; отказ всех порций — ошибка запроса
+        let mut output = PredictOutput::default();
+        let mut last_error = None;
+        for (chunk, result) in chunks.iter().zip(results) {
+            match result {
+                Ok(chunk_output) => output.extend(chunk_output),
+                Err(e) => {
+                    output.extend(PredictOutput::failed(chunk, e.clone()));
+                    last_error = Some(e);
+                }
+            }
+        }
+        match last_error {
+            Some(e) if output.items.is_empty() => Err(e),
+            _ => Ok(output),
+        }
+    }
+
+    fn describe(&self) -> ModelInfo {
+        ModelInfo { endpoint: Some(self.url.clone()), ..ModelInfo::new("proxy", self.loaded_at) }
+    }
+
+    /// Сервис готов, если цепь не разомкнута и он отвечает на пустой батч корректным `/predict`
+    async fn check_ready(&self) -> Result<(), PredictError> {
+        if !self.breaker.is_closed() {
+            return Err(PredictError::Unavailable("circuit breaker is open".to_string()));
+        }
+        self.call(&[]).await.map(|_| ())
+    }
+}
+
+/// Сопоставляет ответ сервиса запрошенным отзывам в порядке запроса
+///
+/// Каждый отзыв получает ровно один ответ. Id, повторённый во входе, ждёт столько же ответов,
+/// они достаются вхождениям по порядку. Ответы на незапрошенные id пропускаются, лишний ответ
+/// на id делает некорректными все его вхождения.
+fn parse_response(samples: &[PredictSample], json: &serde_json::Value) -> Result<PredictOutput, PredictError> {
+    let predictions = json
+        .get("predictions")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| PredictError::MalformedResponse("`predictions` array is missing".to_string()))?;
+
+    let mut requested: HashMap<i64, usize> = HashMap::with_capacity(samples.len());
+    for sample in samples {
+        *requested.entry(sample.id).or_default() += 1;
+    }
+    let mut answers: HashMap<i64, VecDeque<Result<PredictItem, PredictError>>> = HashMap::with_capacity(samples.len());
+    for it in predictions {
+        let Some(id) = it.get("id").and_then(|v| v.as_i64()) else {
+            return Err(PredictError::MalformedResponse("prediction without numeric `id`".to_string()));
+        };
+        let Some(&expected) = requested.get(&id) else {
+            warn!("Proxy predictor returned unexpected id {}", id);
+            continue;
+        };
+        let queue = answers.entry(id).or_default();
+        if queue.len() >= expected {
+            let error = PredictError::MalformedResponse("duplicate prediction for sample".to_string());
+            queue.iter_mut().for_each(|answer| *answer = Err(error.clone()));
+            continue;
+        }
+        queue.push_back(
+            parse_proxy_item(id, it)
+                .ok_or_else(|| PredictError::MalformedResponse("invalid topics/sentiments/scores".to_string())),
+        );
+    }
+
+    let mut output = PredictOutput::default();
+    for sample in samples {
+        match answers.get_mut(&sample.id).and_then(VecDeque::pop_front) {
+            Some(Ok(item)) => output.items.push(item),
+            Some(Err(e)) => output.errors.push((sample.id, e)),
+            None => output
+                .errors
+                .push((sample.id, PredictError::MalformedResponse("sample is missing from proxy response".to_string()))),
+        }
+    }
+    Ok(output)
+}
+
+/// Топики в структурном формате (`[{topic, sentiment, score?}]`) или в формате serve.py —
+/// параллельные `topics`/`sentiments` одинаковой длины и необязательные `topic_scores` (объект
+/// или массив параллельно `topics`). Необязательные `sentiment_scores` и `rejected_topics`,
+/// если присланы, должны быть корректны; любая неизвестная тональность делает ответ некорректным
+fn parse_proxy_item(id: i64, it: &serde_json::Value) -> Option<PredictItem> {
+    let topics = it.get("topics")?.as_array()?;
+    let topics = if topics.iter().all(|t| t.is_object()) {
+        topics.iter().map(parse_topic_sentiment).collect::<Option<Vec<_>>>()?
+    } else {
+        parse_legacy_topics(it, topics)?
+    };
+
+    let mut item = PredictItem::new(id, topics);
+    if let Some(scores) = it.get("sentiment_scores") {
+        item.sentiment_scores = score_map(scores)?
+            .into_iter()
+            .map(|(label, p)| Some((Sentiment::from_label(&label)?.as_str().to_string(), p)))
+            .collect::<Option<_>>()?;
+    }
+    if let Some(rejected) = it.get("rejected_topics") {
+        item.rejected_topics = rejected
+            .as_array()?
+            .iter()
+            .map(|r| Some(TopicScore { topic: r.get("topic")?.as_str()?.to_string(), score: score(r.get("score")?)? }))
+            .collect::<Option<Vec<_>>>()?;
+        item.rejected_topics.truncate(MAX_REJECTED_TOPICS);
+    }
+    Some(item)
+}
+
+fn parse_topic_sentiment(value: &serde_json::Value) -> Option<TopicSentiment> {
+    let topic = value.get("topic")?.as_str()?.to_string();
+    let sentiment = Sentiment::from_label(value.get("sentiment")?.as_str()?)?;
+    let score = match value.get("score") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(v) => Some(score(v)?),
+    };
+    Some(TopicSentiment { score, ..TopicSentiment::new(topic, sentiment) })
+}
+
+fn parse_legacy_topics(it: &serde_json::Value, topics: &[serde_json::Value]) -> Option<Vec<TopicSentiment>> {
+    let sentiments = it.get("sentiments")?.as_array()?;
+    if topics.len() != sentiments.len() {
+        return None;
+    }
+    let mut parsed: Vec<TopicSentiment> = topics
+        .iter()
+        .zip(sentiments)
+        .map(|(t, s)| Some(TopicSentiment::new(t.as_str()?.to_string(), Sentiment::from_label(s.as_str()?)?)))
+        .collect::<Option<_>>()?;
+
+    match it.get("topic_scores") {
+        None => {}
+        Some(serde_json::Value::Array(values)) if values.len() == parsed.len() => {
+            for (topic, value) in parsed.iter_mut().zip(values) {
+                topic.score = Some(score(value)?);
+            }
+        }
+        Some(value) => {
+            let scores = score_map(value)?;
+            for topic in &mut parsed {
+                topic.score = scores.get(&topic.topic).copied();
+            }
+        }
+    }
+    Some(parsed)
+}
+
+fn score(value: &serde_json::Value) -> Option<f32> {
+    value.as_f64().map(|v| v as f32).filter(|v| (0.0..=1.0).contains(v))
+}
+
+fn score_map(value: &serde_json::Value) -> Option<BTreeMap<String, f32>> {
+    value.as_object()?.iter().map(|(k, v)| Some((k.clone(), score(v)?))).collect()
+}
+
+/// Размыкается после `threshold` отказов подряд; по истечении `cooldown`
+/// пропускает один пробный запрос (half-open), успех замыкает цепь.
+/// Результат запроса сообщается через `BreakerPermit`
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self { threshold: threshold.max(1), cooldown, state: Mutex::new(BreakerState::default()) }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Разрешение на запрос; `None` — цепь разомкнута или пробный запрос уже идёт
+    fn allow(&self) -> Option<BreakerPermit<'_>> {
+        let mut state = self.state();
+        match state.opened_at {
+            None => Some(BreakerPermit { breaker: self, probe: false }),
+            Some(opened) if opened.elapsed() >= self.cooldown && !state.probe_in_flight => {
+                state.probe_in_flight = true;
+                Some(BreakerPermit { breaker: self, probe: true })
+            }
+            Some(_) => None,
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state().opened_at.is_none()
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state();
+        if state.opened_at.is_some() {
+            info!("Proxy predictor recovered, closing circuit breaker");
+        }
+        *state = BreakerState::default();
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state();
+        state.consecutive_failures += 1;
+        state.probe_in_flight = false;
+        if state.opened_at.is_some() || state.consecutive_failures >= self.threshold {
+            if state.opened_at.is_none() {
+                warn!("Proxy predictor failed {} times in a row, opening circuit breaker", state.consecutive_failures);
+            }
+            state.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+/// Разрешение на один запрос через цепь. Если запрос отменён до `record_*` (клиент отключился),
+/// пробный запрос снимается при drop, иначе цепь осталась бы разомкнутой навсегда
+struct BreakerPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl BreakerPermit<'_> {
+    fn record_success(mut self) {
+        self.probe = false;
+        self.breaker.record_success();
+    }
+
+    fn record_failure(mut self) {
+        self.probe = false;
+        self.breaker.record_failure();
+    }
+}
+
+impl Drop for BreakerPermit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.state().probe_in_flight = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpResponse, HttpServer};
+    use serde_json::{json, Value};
+
+    fn samples(ids: &[i64]) -> Vec<PredictSample> {
+        ids.iter().map(|&id| PredictSample { id, text: format!("отзыв {}", id), date: None, region: None }).collect()
+    }
+
+    fn answer(id: i64, topic: &str) -> Value {
+        json!({"id": id, "topics": [topic], "sentiments": ["нейтрально"]})
+    }
+
+    /// Ответы в порядке запроса: топик ответа либо код ошибки
+    fn outcome(samples: &[PredictSample], output: &PredictOutput) -> Vec<(i64, String)> {
+        let (mut items, mut errors) = (output.items.iter().peekable(), output.errors.iter());
+        samples
+            .iter()
+            .map(|s| match items.next_if(|item| item.id == s.id) {
+                Some(item) => (s.id, item.topics[0].topic.clone()),
+                None => errors.next().map(|(id, e)| (*id, e.code().to_string())).expect("sample has an error"),
+            })
+            .collect()
+    }
+
+    fn expect(pairs: &[(i64, &str)]) -> Vec<(i64, String)> {
+        pairs.iter().map(|(id, s)| (*id, s.to_string())).collect()
+    }
+
+    #[test]
+    fn response_is_matched_to_request_order() {
+        let samples = samples(&[1, 2, 3]);
+        let json = json!({"predictions": [answer(3, "Карты"), answer(99, "Сайт"), answer(1, "Ипотека"), answer(2, "Вклады")]});
+        let output = parse_response(&samples, &json).expect("valid response");
+        assert_eq!(output.items.iter().map(|i| i.id).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(outcome(&samples, &output), expect(&[(1, "Ипотека"), (2, "Вклады"), (3, "Карты")]));
+    }
+
+    #[test]
+    fn missing_duplicate_and_invalid_answers_are_per_sample_errors() {
+        let samples = samples(&[1, 2, 3, 4]);
+        let invalid = json!({"id": 4, "topics": ["Карты"], "sentiments": ["странно"]});
+        let json = json!({"predictions": [answer(1, "Ипотека"), answer(2, "Карты"), answer(2, "Вклады"), invalid]});
+        let output = parse_response(&samples, &json).expect("valid response");
+        assert_eq!(
+            outcome(&samples, &output),
+            expect(&[(1, "Ипотека"), (2, "malformed_response"), (3, "malformed_response"), (4, "malformed_response")])
+        );
+    }
+
+    #[test]
+    fn repeated_request_ids_take_answers_in_order() {
+        let samples = samples(&[7, 8, 7]);
+        let json = json!({"predictions": [answer(7, "Ипотека"), answer(8, "Карты"), answer(7, "Вклады")]});
+        let output = parse_response(&samples, &json).expect("valid response");
+        assert!(output.errors.is_empty());
+        assert_eq!(outcome(&samples, &output), expect(&[(7, "Ипотека"), (8, "Карты"), (7, "Вклады")]));
+
+        // Один ответ на два вхождения: второе вхождение без ответа
+        let json = json!({"predictions": [answer(7, "Ипотека"), answer(8, "Карты")]});
+        let output = parse_response(&samples, &json).expect("valid response");
+        assert_eq!(outcome(&samples, &output), expect(&[(7, "Ипотека"), (8, "Карты"), (7, "malformed_response")]));
+    }
+
+    #[test]
+    fn broken_response_fails_whole_request() {
+        let samples = samples(&[1]);
+        for json in [json!({}), json!({"predictions": {}}), json!({"predictions": [{"id": "1", "topics": []}]})] {
+            assert!(matches!(parse_response(&samples, &json), Err(PredictError::MalformedResponse(_))), "{json}");
+        }
+    }
+
+    /// Отвечает в обратном порядке; порция с id 1 отвечает последней
+    async fn reversed(body: web::Json<Value>) -> HttpResponse {
+        let data = body["data"].as_array().cloned().unwrap_or_default();
+        if data.first().and_then(|s| s["id"].as_i64()) == Some(1) {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let predictions: Vec<Value> = data.iter().rev().map(|s| answer(s["id"].as_i64().unwrap_or(0), "Карты")).collect();
+        HttpResponse::Ok().json(json!({ "predictions": predictions }))
+    }
+
+    #[actix_web::test]
+    async fn chunks_are_stitched_in_request_order() {
+        let server = HttpServer::new(|| App::new().route("/predict", web::post().to(reversed)))
+            .workers(1)
+            .bind(("127.0.0.1", 0))
+            .expect("bind test server");
+        let url = format!("http://{}/predict", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+
+        let settings = ProxySettings {
+            request_timeout: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(5),
+            retries: 0,
+            backoff: Duration::ZERO,
+            breaker_threshold: 3,
+            breaker_cooldown: Duration::from_secs(1),
+            chunk_size: 2,
+            concurrency: 3,
+        };
+        let proxy = ProxyPredictor::new(url, settings).expect("proxy predictor");
+        let output = proxy.predict(&samples(&[1, 2, 3, 4, 5])).await.expect("proxy answered");
+        assert!(output.errors.is_empty());
+        assert_eq!(output.items.iter().map(|i| i.id).collect::<Vec<_>>(), [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn dropped_probe_lets_next_probe_close_breaker() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        breaker.allow().expect("closed breaker allows").record_failure();
+        assert!(!breaker.is_closed());
+
+        let probe = breaker.allow().expect("half-open breaker allows a probe");
+        assert!(breaker.allow().is_none(), "only one probe at a time");
+        // Отменённый запрос: результат так и не записан
+        drop(probe);
+
+        breaker.allow().expect("probe is allowed again").record_success();
+        assert!(breaker.is_closed());
+    }
+}
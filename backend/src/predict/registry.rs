@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::domain::{ModelInfo, ModelVersionItem, PredictSample};
+use crate::predict::shadow::ShadowStats;
+use crate::predict::{PredictError, PredictOutput, Predictor};
+
+/// Файлы версии в `MODEL_DIR`: `v43_model.onnx`, `v43_artifacts.json`, `v43_manifest.json`
+static VERSION_FILE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(v(\d+))_(model\.onnx|artifacts\.json|manifest\.json)$").expect("version file regex"));
+
+/// Имя версии, когда в `MODEL_DIR` нет ни одной версионированной модели
+pub const UNVERSIONED: &str = "default";
+
+/// `<version>_manifest.json`; пути задаются относительно `MODEL_DIR`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ModelManifest {
+    onnx: Option<PathBuf>,
+    artifacts: Option<PathBuf>,
+    /// Каталог с `tokenizer.json` или `vocab.txt`
+    tokenizer: Option<PathBuf>,
+    tokenizer_lowercase: Option<bool>,
+    /// Метки топиков в порядке столбцов `logits` ONNX-модели
+    labels: Option<Vec<String>>,
+    topic_threshold: Option<f32>,
+}
+
+/// Одна версия модели: файлы и параметры, с которыми её нужно загрузить
+#[derive(Debug, Clone)]
+pub struct ModelVersion {
+    pub name: String,
+    pub onnx_path: Option<PathBuf>,
+    pub artifacts_path: Option<PathBuf>,
+    pub tokenizer_dir: PathBuf,
+    pub tokenizer_lowercase: bool,
+    pub topic_labels: Option<Vec<String>>,
+    pub topic_threshold: f32,
+}
+
+impl ModelVersion {
+    /// Версия без файлов модели (Proxy или Mock) с параметрами из конфигурации
+    pub fn unversioned(config: &Config) -> Self {
+        Self {
+            name: UNVERSIONED.to_string(),
+            onnx_path: None,
+            artifacts_path: None,
+            tokenizer_dir: config.model_dir.clone(),
+            tokenizer_lowercase: config.tokenizer_lowercase,
+            topic_labels: None,
+            topic_threshold: config.topic_threshold,
+        }
+    }
+}
+
+/// Находит версии моделей в `MODEL_DIR`, упорядоченные по номеру версии
+pub fn discover(config: &Config) -> Result<Vec<ModelVersion>> {
+    let dir = &config.model_dir;
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(Vec::new());
+    };
+
+    let mut found: HashMap<String, (u64, Vec<String>)> = HashMap::new();
+    for entry in entries.flatten() {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let Some(caps) = VERSION_FILE_RE.captures(&file_name) else { continue };
+        let number = caps[2].parse().unwrap_or(u64::MAX);
+        found.entry(caps[1].to_string()).or_insert_with(|| (number, Vec::new())).1.push(caps[3].to_string());
+    }
+
+    let mut found: Vec<_> = found.into_iter().collect();
+    found.sort_by_key(|(_, (number, _))| *number);
+
+    let mut versions = Vec::with_capacity(found.len());
+    for (name, (_, kinds)) in found {
+        let file = |kind: &str| kinds.iter().any(|k| k == kind).then(|| dir.join(format!("{}_{}", name, kind)));
+        let manifest = match file("manifest.json") {
+            Some(path) => read_manifest(&path)?,
+            None => ModelManifest::default(),
+        };
+        let resolve = |path: PathBuf| if path.is_absolute() { path } else { dir.join(path) };
+
+        versions.push(ModelVersion {
+            onnx_path: manifest.onnx.map(resolve).or_else(|| file("model.onnx")),
+            artifacts_path: manifest.artifacts.map(resolve).or_else(|| file("artifacts.json")),
+            tokenizer_dir: manifest.tokenizer.map(resolve).unwrap_or_else(|| dir.clone()),
+            tokenizer_lowercase: manifest.tokenizer_lowercase.unwrap_or(config.tokenizer_lowercase),
+            topic_labels: manifest.labels,
+            topic_threshold: manifest.topic_threshold.unwrap_or(config.topic_threshold),
+            name,
+        });
+    }
+    Ok(versions)
+}
+
+fn read_manifest(path: &Path) -> Result<ModelManifest> {
+    let content = fs::read_to_string(path).with_context(|| format!("failed to read model manifest {:?}", path))?;
+    let manifest: ModelManifest =
+        serde_json::from_str(&content).with_context(|| format!("invalid model manifest {:?}", path))?;
+    if let Some(threshold) = manifest.topic_threshold
+        && !(threshold > 0.0 && threshold < 1.0)
+    {
+        bail!("model manifest {:?}: topic_threshold must be in (0, 1), got {}", path, threshold);
+    }
+    if manifest.labels.as_ref().is_some_and(|labels| labels.is_empty()) {
+        bail!("model manifest {:?}: labels must not be empty", path);
+    }
+    Ok(manifest)
+}
+
+/// Загруженные версии модели; запросы без явной версии обслуживает версия по умолчанию
+pub struct ModelRegistry {
+    models: BTreeMap<String, Arc<dyn Predictor>>,
+    default: String,
+    /// Сравнение версии по умолчанию с теневыми, если они настроены
+    shadow_stats: Option<Arc<ShadowStats>>,
+}
+
+impl ModelRegistry {
+    pub fn new(models: BTreeMap<String, Arc<dyn Predictor>>, default: String) -> Result<Self> {
+        if !models.contains_key(&default) {
+            bail!("default model {} is not loaded", default);
+        }
+        Ok(Self { models, default, shadow_stats: None })
+    }
+
+    pub fn with_shadow_stats(mut self, stats: Arc<ShadowStats>) -> Self {
+        self.shadow_stats = Some(stats);
+        self
+    }
+
+    pub fn shadow_stats(&self) -> Option<&Arc<ShadowStats>> {
+        self.shadow_stats.as_ref()
+    }
+
+    /// Предиктор версии `name` или версии по умолчанию
+    pub fn get(&self, name: Option<&str>) -> Option<Arc<dyn Predictor>> {
+        self.models.get(name.unwrap_or(&self.default)).cloned()
+    }
+
+    pub fn default_version(&self) -> &str {
+        &self.default
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Arc<dyn Predictor>)> {
+        self.models.iter().map(|(name, predictor)| (name.as_str(), predictor))
+    }
+
+    /// Описание всех версий для `/models`
+    pub fn list(&self) -> Vec<ModelVersionItem> {
+        self.iter()
+            .map(|(name, predictor)| ModelVersionItem {
+                version: name.to_string(),
+                default: name == self.default,
+                info: predictor.describe(),
+            })
+            .collect()
+    }
+
+    fn default_predictor(&self) -> &Arc<dyn Predictor> {
+        &self.models[&self.default]
+    }
+}
+
+#[async_trait]
+impl Predictor for ModelRegistry {
+    async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        self.default_predictor().predict(samples).await
+    }
+
+    fn describe(&self) -> ModelInfo {
+        ModelInfo { version: Some(self.default.clone()), ..self.default_predictor().describe() }
+    }
+
+    async fn check_ready(&self) -> Result<(), PredictError> {
+        self.default_predictor().check_ready().await
+    }
+}
@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::domain::{ModelInfo, ModelVersionItem, PredictSample};
+use crate::metrics;
+use crate::predict::registry::ModelRegistry;
+use crate::predict::rules::SharedRules;
+use crate::predict::{factory, self_test, PredictError, PredictOutput, Predictor};
+use crate::taxonomy::SharedTaxonomy;
+
+/// Реестр моделей с атомарной подменой: каждый запрос берёт текущий `Arc` и
+/// дорабатывает на нём, даже если модели уже заменили
+pub struct ReloadablePredictor {
+    current: RwLock<Arc<ModelRegistry>>,
+}
+
+impl ReloadablePredictor {
+    pub fn new(registry: ModelRegistry) -> Self {
+        Self { current: RwLock::new(Arc::new(registry)) }
+    }
+
+    /// Снимок активного реестра
+    pub fn registry(&self) -> Arc<ModelRegistry> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Предиктор версии `name` (для длинных задач — одна модель на всю задачу)
+    pub fn get(&self, name: Option<&str>) -> Option<Arc<dyn Predictor>> {
+        self.registry().get(name)
+    }
+
+    fn swap(&self, registry: ModelRegistry) -> Arc<ModelRegistry> {
+        std::mem::replace(&mut *self.current.write().unwrap_or_else(|e| e.into_inner()), Arc::new(registry))
+    }
+}
+
+#[async_trait]
+impl Predictor for ReloadablePredictor {
+    async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        self.registry().predict(samples).await
+    }
+
+    fn describe(&self) -> ModelInfo {
+        self.registry().describe()
+    }
+
+    async fn check_ready(&self) -> Result<(), PredictError> {
+        self.registry().check_ready().await
+    }
+}
+
+/// Загружает новую модель в фоне, проверяет её на эталонных текстах и подменяет активную
+pub struct ModelReloader {
+    config: Config,
+    taxonomy: Arc<SharedTaxonomy>,
+    rules: Arc<SharedRules>,
+    predictor: Arc<ReloadablePredictor>,
+    /// Одновременно выполняется только одна перезагрузка
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl ModelReloader {
+    pub fn new(
+        config: Config,
+        taxonomy: Arc<SharedTaxonomy>,
+        rules: Arc<SharedRules>,
+        predictor: Arc<ReloadablePredictor>,
+    ) -> Self {
+        Self { config, taxonomy, rules, predictor, lock: tokio::sync::Mutex::new(()) }
+    }
+
+    /// Каждая версия должна загрузиться без деградации до Mock и пройти самопроверку,
+    /// иначе продолжают работать старые
+    pub async fn reload(&self) -> Result<Vec<ModelVersionItem>> {
+        let _guard = self.lock.lock().await;
+        let result = self.load_candidate().await;
+        metrics::MODEL_RELOADS.with_label_values(&[if result.is_ok() { "success" } else { "failure" }]).inc();
+
+        let candidate = result?;
+        let models = candidate.list();
+        info!(
+            "Models reloaded: {} (default {})",
+            models.iter().map(|m| m.version.as_str()).collect::<Vec<_>>().join(", "),
+            candidate.default_version()
+        );
+        drop(self.predictor.swap(candidate));
+        Ok(models)
+    }
+
+    async fn load_candidate(&self) -> Result<ModelRegistry> {
+        let (config, taxonomy, rules) = (self.config.clone(), self.taxonomy.clone(), self.rules.clone());
+        let candidate = tokio::task::spawn_blocking(move || factory::build(&config, &taxonomy, &rules, true))
+            .await
+            .context("model loading task panicked")??;
+        self_test::run_registry(&candidate).await.context("new model failed self-test")?;
+        Ok(candidate)
+    }
+
+    /// Периодически сверяет файлы `MODEL_DIR` и перезагружает модель после того,
+    /// как изменения перестали происходить (файл докопирован)
+    pub fn spawn_watcher(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let dir = self.config.model_dir.clone();
+            let mut loaded = fingerprint(&dir);
+            let mut pending: Option<Vec<FileStamp>> = None;
+            info!("Watching {:?} for model changes every {:?}", dir, interval);
+
+            loop {
+                tokio::time::sleep(interval).await;
+                let current = fingerprint(&dir);
+                if current == loaded {
+                    pending = None;
+                    continue;
+                }
+                if pending.as_ref() != Some(&current) {
+                    pending = Some(current);
+                    continue;
+                }
+
+                info!("Model files in {:?} changed, reloading", dir);
+                if let Err(e) = self.reload().await {
+                    error!("Model reload failed, keeping current predictor: {:#}", e);
+                }
+                // Неудачную версию не перезагружаем повторно, ждём следующего изменения
+                loaded = current;
+                pending = None;
+            }
+        });
+    }
+}
+
+type FileStamp = (PathBuf, u64, Option<SystemTime>);
+
+fn fingerprint(dir: &Path) -> Vec<FileStamp> {
+    let mut stamps: Vec<FileStamp> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|entry| {
+                let meta = entry.metadata().ok()?;
+                meta.is_file().then(|| (entry.path(), meta.len(), meta.modified().ok()))
+            })
+            .collect(),
+        Err(e) => {
+            warn!("Failed to read model directory {:?}: {}", dir, e);
+            Vec::new()
+        }
+    };
+    stamps.sort();
+    stamps
+}
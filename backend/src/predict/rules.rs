@@ -0,0 +1,858 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::domain::{FiredRule, RulesExplanation, RulesInfo, Sentiment, TextSpan, TopicSentiment};
+use crate::metrics;
+
+/// Правила по умолчанию; формат описан в самом файле
+const BUILTIN_RULES: &str = include_str!("rules.yaml");
+
+static WORD_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\w+").expect("word regex"));
+
+/// Границы предложения: противопоставление действует только внутри него
+const SENTENCE_BREAKS: &[char] = &['.', '!', '?', '\n'];
+/// Границы фразы внутри предложения: через них не действуют отрицание и усилители
+const CLAUSE_BREAKS: &[char] = &[',', ';', ':'];
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    negation: NegationSpec,
+    #[serde(default)]
+    scoring: ScoringSpec,
+    default_topic: Option<String>,
+    rules: Vec<RuleSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct NegationSpec {
+    particles: Vec<String>,
+    window: usize,
+    scale: f32,
+}
+
+impl Default for NegationSpec {
+    fn default() -> Self {
+        Self { particles: ["не", "нет", "ни", "без"].map(String::from).to_vec(), window: 3, scale: 0.75 }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ScoringSpec {
+    intensifiers: HashMap<String, f32>,
+    contrast: ContrastSpec,
+    neutral_threshold: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ContrastSpec {
+    words: Vec<String>,
+    before: f32,
+    after: f32,
+}
+
+impl Default for ContrastSpec {
+    fn default() -> Self {
+        Self { words: Vec::new(), before: 1.0, after: 1.0 }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    id: String,
+    #[serde(default)]
+    stems: Vec<String>,
+    #[serde(default)]
+    words: Vec<String>,
+    #[serde(default)]
+    regex: Vec<String>,
+    topic: Option<String>,
+    sentiment: Option<Sentiment>,
+    #[serde(default)]
+    priority: i32,
+    /// Сила тональности правила
+    #[serde(default = "default_weight")]
+    weight: f32,
+    on_negation: Option<OnNegation>,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+/// Поведение правила, если перед совпадением стоит частица отрицания
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OnNegation {
+    Keep,
+    Flip,
+    Skip,
+}
+
+struct Rule {
+    id: String,
+    pattern: Regex,
+    topic: Option<String>,
+    sentiment: Option<Sentiment>,
+    priority: i32,
+    weight: f32,
+    on_negation: OnNegation,
+}
+
+/// Фраза текста (границы в байтах без крайних пробелов) и её вес в оценке тональности
+struct Clause {
+    start: usize,
+    end: usize,
+    sentence: usize,
+    /// Часть предложения между противительными союзами
+    segment: usize,
+    weight: f32,
+}
+
+/// Совпадение правила в тексте; границы в байтах
+struct Hit<'a> {
+    rule: &'a Rule,
+    start: usize,
+    end: usize,
+    /// Индекс фразы; нет, если совпадение начинается со знака препинания
+    clause: Option<usize>,
+    negated: bool,
+    applied: bool,
+    sentiment: Option<Sentiment>,
+    /// Вклад в оценку тональности
+    score: Option<f32>,
+}
+
+/// Фразы текста и совпадения правил в них
+struct Analysis<'a> {
+    clauses: Vec<Clause>,
+    hits: Vec<Hit<'a>>,
+}
+
+impl Analysis<'_> {
+    fn mentions_topic(&self, clause: usize) -> bool {
+        self.hits.iter().any(|h| h.applied && h.clause == Some(clause) && h.rule.topic.is_some())
+    }
+
+    /// Фразы, по которым оценивается топик; `None` — топик в тексте не упомянут
+    ///
+    /// Фраза без топика относится к ближайшей предыдущей фразе с топиком (в начале — к следующей)
+    /// той же части предложения: союз «но» отделяет оценку от топика до него. Фраза, для которой
+    /// такой нет, в предложении с топиками не учитывается; предложение без топиков — общее для всех.
+    fn topic_clauses(&self, topic: &str) -> Option<Vec<usize>> {
+        let topic = topic.to_lowercase();
+        let mentions = |clause: usize| {
+            self.hits.iter().any(|h| {
+                h.applied && h.clause == Some(clause) && h.rule.topic.as_ref().is_some_and(|t| t.to_lowercase() == topic)
+            })
+        };
+        if !(0..self.clauses.len()).any(mentions) {
+            return None;
+        }
+
+        let mut owned = Vec::new();
+        for (i, clause) in self.clauses.iter().enumerate() {
+            let segment = |&j: &usize| self.clauses[j].segment == clause.segment;
+            let owner = (0..=i)
+                .rev()
+                .take_while(segment)
+                .find(|&j| self.mentions_topic(j))
+                .or_else(|| (i + 1..self.clauses.len()).take_while(segment).find(|&j| self.mentions_topic(j)));
+            let shared = || {
+                (0..self.clauses.len())
+                    .filter(|&j| self.clauses[j].sentence == clause.sentence)
+                    .all(|j| !self.mentions_topic(j))
+            };
+            if owner.map_or_else(shared, mentions) {
+                owned.push(i);
+            }
+        }
+        Some(owned)
+    }
+}
+
+/// Скомпилированные правила ключевых слов: топики и тональность по тексту
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    particles: HashSet<String>,
+    window: usize,
+    /// Множитель силы тональности под отрицанием
+    negation_scale: f32,
+    intensifiers: HashMap<String, f32>,
+    /// Противительные союзы: фраза после союза весит `contrast_after`, до него — `contrast_before`
+    contrast: Option<Regex>,
+    contrast_before: f32,
+    contrast_after: f32,
+    /// Оценки по модулю меньше порога считаются нейтральными
+    neutral_threshold: f32,
+    default_topic: Option<String>,
+    info: RulesInfo,
+}
+
+impl RuleSet {
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_RULES, false, "builtin".to_string()).expect("builtin keyword rules are valid")
+    }
+
+    /// Файл `.json` разбирается как JSON, остальные — как YAML
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("failed to read rules {:?}", path))?;
+        let json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        Self::parse(&content, json, path.display().to_string()).with_context(|| format!("invalid rules {:?}", path))
+    }
+
+    fn parse(content: &str, json: bool, source: String) -> Result<Self> {
+        let file: RulesFile = if json { serde_json::from_str(content)? } else { serde_yaml::from_str(content)? };
+
+        let mut ids = HashSet::new();
+        let mut rules = Vec::with_capacity(file.rules.len());
+        for spec in file.rules {
+            if !ids.insert(spec.id.clone()) {
+                bail!("duplicate rule id {:?}", spec.id);
+            }
+            rules.push(compile(spec)?);
+        }
+        let scoring = file.scoring;
+        let contrast = match scoring.contrast.words.as_slice() {
+            [] => None,
+            words => {
+                let words: Vec<String> = words.iter().map(|w| regex::escape(w.trim())).collect();
+                Some(Regex::new(&format!(r"(?i)\b(?:{})\b", words.join("|")))?)
+            }
+        };
+        Ok(Self {
+            info: RulesInfo { source, rules: rules.len(), loaded_at: chrono::Utc::now() },
+            rules,
+            particles: file.negation.particles.iter().map(|p| p.to_lowercase()).collect(),
+            window: file.negation.window,
+            negation_scale: file.negation.scale,
+            intensifiers: scoring.intensifiers.into_iter().map(|(word, k)| (word.to_lowercase(), k)).collect(),
+            contrast,
+            contrast_before: scoring.contrast.before,
+            contrast_after: scoring.contrast.after,
+            neutral_threshold: scoring.neutral_threshold,
+            default_topic: file.default_topic,
+        })
+    }
+
+    pub fn info(&self) -> &RulesInfo {
+        &self.info
+    }
+
+    /// Все топики, которые могут дать правила, в порядке файла
+    pub fn topics(&self) -> Vec<String> {
+        let mut topics: Vec<String> = Vec::new();
+        for topic in self.rules.iter().filter_map(|r| r.topic.as_ref()).chain(&self.default_topic) {
+            if !topics.contains(topic) {
+                topics.push(topic.clone());
+            }
+        }
+        topics
+    }
+
+    pub fn default_topic(&self) -> Option<&str> {
+        self.default_topic.as_deref()
+    }
+
+    /// Тональность каждого из `topics` по фрагментам текста, где он упомянут (см. `evaluate`)
+    ///
+    /// Топикам, которых правила в тексте не нашли (метки модели), достаётся тональность всего текста.
+    pub fn aspects(&self, text: &str, topics: &[String]) -> Vec<TopicSentiment> {
+        let analysis = self.analyze(text);
+        topics.iter().map(|topic| self.aspect(text, &analysis, topic)).collect()
+    }
+
+    /// Применяет правила к тексту
+    ///
+    /// Топики идут по убыванию приоритета, затем в порядке файла. Тональность — сумма вкладов
+    /// правил: вес правила с учётом отрицания и усилителей перед совпадением, умноженный
+    /// на вес фразы (противопоставление внутри предложения). Тональность топика считается
+    /// только по фразам, которые к нему относятся; они возвращаются в `spans` топика.
+    pub fn evaluate(&self, text: &str) -> RulesExplanation {
+        let analysis = self.analyze(text);
+
+        let mut topics: Vec<(i32, &str)> = Vec::new();
+        for hit in analysis.hits.iter().filter(|h| h.applied) {
+            if let Some(topic) = &hit.rule.topic
+                && !topics.iter().any(|(_, t)| t == topic)
+            {
+                topics.push((hit.rule.priority, topic));
+            }
+        }
+        topics.sort_by_key(|&(priority, _)| std::cmp::Reverse(priority));
+        let mut names: Vec<&str> = topics.into_iter().map(|(_, t)| t).collect();
+        if names.is_empty()
+            && let Some(topic) = &self.default_topic
+        {
+            names.push(topic);
+        }
+        let topics = names.into_iter().map(|topic| self.aspect(text, &analysis, topic)).collect();
+
+        let judged = self.judge(analysis.hits.iter().filter_map(|h| h.score));
+        let mut fired: Vec<FiredRule> = analysis
+            .hits
+            .iter()
+            .map(|hit| FiredRule {
+                rule: hit.rule.id.clone(),
+                matched: text[hit.start..hit.end].to_string(),
+                start: char_offset(text, hit.start),
+                end: char_offset(text, hit.end),
+                negated: hit.negated,
+                applied: hit.applied,
+                topic: hit.rule.topic.clone(),
+                sentiment: hit.sentiment,
+                score: hit.score,
+                priority: hit.rule.priority,
+            })
+            .collect();
+        fired.sort_by_key(|f| f.start);
+        RulesExplanation { topics, sentiment: judged.map(|(s, _)| s), score: judged.map(|(_, p)| p), fired }
+    }
+
+    fn analyze(&self, text: &str) -> Analysis<'_> {
+        let clauses = self.clauses(text);
+        let mut hits = Vec::new();
+        for rule in &self.rules {
+            for m in rule.pattern.find_iter(text) {
+                let clause = clauses.iter().position(|c| c.start <= m.start() && m.start() < c.end);
+                let (clause_start, clause_weight) = clause.map_or((m.start(), 1.0), |i| (clauses[i].start, clauses[i].weight));
+                let (negated, intensity) = self.modifiers(&text[clause_start..m.start()]);
+                let applied = !(negated && rule.on_negation == OnNegation::Skip);
+                let flipped = negated && rule.on_negation == OnNegation::Flip;
+                let sentiment = rule.sentiment.map(|s| if flipped { flip(s) } else { s });
+                let score = rule.sentiment.filter(|_| applied).map(|s| {
+                    let negation = if flipped { -self.negation_scale } else { 1.0 };
+                    polarity(s) * rule.weight * intensity * negation * clause_weight
+                });
+                hits.push(Hit { rule, start: m.start(), end: m.end(), clause, negated, applied, sentiment, score });
+            }
+        }
+        Analysis { clauses, hits }
+    }
+
+    fn aspect(&self, text: &str, analysis: &Analysis, topic: &str) -> TopicSentiment {
+        let (judged, spans) = match analysis.topic_clauses(topic) {
+            Some(owned) => {
+                let scores = analysis
+                    .hits
+                    .iter()
+                    .filter(|h| h.clause.is_none_or(|c| owned.contains(&c)))
+                    .filter_map(|h| h.score);
+                (self.judge(scores), text_spans(text, &analysis.clauses, &owned))
+            }
+            None => (self.judge(analysis.hits.iter().filter_map(|h| h.score)), Vec::new()),
+        };
+        let (sentiment, polarity) = judged.map_or((Sentiment::Neutral, None), |(s, p)| (s, Some(p)));
+        TopicSentiment { polarity, spans, ..TopicSentiment::new(topic.to_string(), sentiment) }
+    }
+
+    /// Тональность по вкладам правил; `None` — правила тональности не сработали
+    fn judge(&self, scores: impl Iterator<Item = f32>) -> Option<(Sentiment, f32)> {
+        let mut scores = scores.peekable();
+        scores.peek()?;
+        let total: f32 = scores.sum();
+        // Сумма вкладов не ограничена, x / sqrt(x² + 1) сжимает её в (-1, 1)
+        let score = total / (total * total + 1.0).sqrt();
+        let sentiment = if score > 0.0 && score >= self.neutral_threshold {
+            Sentiment::Positive
+        } else if score < 0.0 && score <= -self.neutral_threshold {
+            Sentiment::Negative
+        } else {
+            Sentiment::Neutral
+        };
+        Some((sentiment, score))
+    }
+
+    /// Делит текст на фразы по знакам препинания и перед противительными союзами;
+    /// в предложении с союзом фразы до последнего союза ослабляются, после — усиливаются
+    fn clauses(&self, text: &str) -> Vec<Clause> {
+        let mut clauses = Vec::new();
+        let mut segment = 0;
+        for (sentence, (sentence_start, sentence_end)) in spans(text, 0, text.len(), SENTENCE_BREAKS).into_iter().enumerate() {
+            let first = clauses.len();
+            let mut contrast_at = None;
+            if first > 0 {
+                segment += 1;
+            }
+            for (start, end) in spans(text, sentence_start, sentence_end, CLAUSE_BREAKS) {
+                let mut clause_start = start;
+                for m in self.contrast.iter().flat_map(|re| re.find_iter(&text[start..end])) {
+                    if !text[clause_start..start + m.start()].trim().is_empty() {
+                        let (start, end) = trimmed(text, clause_start, start + m.start());
+                        clauses.push(Clause { start, end, sentence, segment, weight: 1.0 });
+                    }
+                    clause_start = start + m.start();
+                    contrast_at = Some(clauses.len());
+                    segment += 1;
+                }
+                clauses.push(Clause { start: clause_start, end, sentence, segment, weight: 1.0 });
+            }
+            if let Some(at) = contrast_at {
+                for (i, clause) in clauses.iter_mut().enumerate().skip(first) {
+                    clause.weight = if i < at { self.contrast_before } else { self.contrast_after };
+                }
+            }
+        }
+        clauses
+    }
+
+    /// Отрицание и множитель усилителей среди последних `window` слов перед совпадением
+    ///
+    /// Усилитель между отрицанием и словом не учитывается: «не очень хорошо» — слабая оценка,
+    /// а «совсем не понравилось» — сильная.
+    fn modifiers(&self, before: &str) -> (bool, f32) {
+        let words: Vec<&str> = WORD_RE.find_iter(before).map(|m| m.as_str()).collect();
+        let (mut negated, mut intensity) = (false, 1.0);
+        for word in words.iter().rev().take(self.window).map(|w| w.to_lowercase()) {
+            if self.particles.contains(&word) {
+                negated = true;
+                intensity = 1.0;
+            } else if let Some(k) = self.intensifiers.get(&word) {
+                intensity *= k;
+            }
+        }
+        (negated, intensity)
+    }
+}
+
+/// Непустые куски `text[start..end]` между разделителями, без крайних пробелов
+fn spans(text: &str, start: usize, end: usize, breaks: &[char]) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut from = start;
+    for (i, c) in text[start..end].char_indices() {
+        if breaks.contains(&c) {
+            if !text[from..start + i].trim().is_empty() {
+                spans.push(trimmed(text, from, start + i));
+            }
+            from = start + i + c.len_utf8();
+        }
+    }
+    if !text[from..end].trim().is_empty() {
+        spans.push(trimmed(text, from, end));
+    }
+    spans
+}
+
+fn trimmed(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let piece = &text[start..end];
+    (start + piece.len() - piece.trim_start().len(), start + piece.trim_end().len())
+}
+
+/// Фрагменты текста из фраз; соседние фразы одного предложения объединяются
+fn text_spans(text: &str, clauses: &[Clause], owned: &[usize]) -> Vec<TextSpan> {
+    let mut ranges: Vec<(usize, usize, usize)> = Vec::new();
+    for &i in owned {
+        let clause = &clauses[i];
+        match ranges.last_mut() {
+            Some((_, end, last)) if *last + 1 == i && clauses[*last].sentence == clause.sentence => {
+                *end = clause.end;
+                *last = i;
+            }
+            _ => ranges.push((clause.start, clause.end, i)),
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|(start, end, _)| TextSpan {
+            start: char_offset(text, start),
+            end: char_offset(text, end),
+            text: text[start..end].to_string(),
+        })
+        .collect()
+}
+
+fn char_offset(text: &str, byte: usize) -> usize {
+    text[..byte].chars().count()
+}
+
+fn compile(spec: RuleSpec) -> Result<Rule> {
+    if spec.topic.is_none() && spec.sentiment.is_none() {
+        bail!("rule {:?} has neither topic nor sentiment", spec.id);
+    }
+    // Слова фразы разделяются любыми пробелами; основа — начало слова, слово — целиком
+    let phrase = |s: &str| s.split_whitespace().map(regex::escape).collect::<Vec<_>>().join(r"\s+");
+    let mut alternatives: Vec<String> = Vec::new();
+    alternatives.extend(spec.stems.iter().map(|s| format!(r"\b{}\w*", phrase(s))));
+    alternatives.extend(spec.words.iter().map(|w| format!(r"\b{}\b", phrase(w))));
+    alternatives.extend(spec.regex.iter().map(|r| format!("(?:{})", r)));
+    if alternatives.is_empty() {
+        bail!("rule {:?} has no stems, words or regex", spec.id);
+    }
+    let pattern = Regex::new(&format!("(?i){}", alternatives.join("|")))
+        .with_context(|| format!("rule {:?}: invalid pattern", spec.id))?;
+
+    let on_negation = spec
+        .on_negation
+        .unwrap_or(if spec.sentiment.is_some() { OnNegation::Flip } else { OnNegation::Keep });
+    Ok(Rule {
+        id: spec.id,
+        pattern,
+        topic: spec.topic,
+        sentiment: spec.sentiment,
+        priority: spec.priority,
+        weight: spec.weight,
+        on_negation,
+    })
+}
+
+fn flip(sentiment: Sentiment) -> Sentiment {
+    match sentiment {
+        Sentiment::Positive => Sentiment::Negative,
+        Sentiment::Negative => Sentiment::Positive,
+        Sentiment::Neutral => Sentiment::Neutral,
+    }
+}
+
+fn polarity(sentiment: Sentiment) -> f32 {
+    match sentiment {
+        Sentiment::Positive => 1.0,
+        Sentiment::Neutral => 0.0,
+        Sentiment::Negative => -1.0,
+    }
+}
+
+/// Активные правила с атомарной подменой при перезагрузке файла
+pub struct SharedRules {
+    current: RwLock<Arc<RuleSet>>,
+    path: Option<PathBuf>,
+}
+
+impl SharedRules {
+    /// Правила из `path` или встроенные
+    pub fn load(path: Option<PathBuf>) -> Result<Self> {
+        let rules = match &path {
+            Some(path) => RuleSet::load(path)?,
+            None => RuleSet::builtin(),
+        };
+        info!("Keyword rules loaded from {}: {} rules", rules.info().source, rules.info().rules);
+        Ok(Self { current: RwLock::new(Arc::new(rules)), path })
+    }
+
+    pub fn current(&self) -> Arc<RuleSet> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Перечитывает файл правил; при ошибке остаются прежние правила
+    pub fn reload(&self) -> Result<RulesInfo> {
+        let result = match &self.path {
+            Some(path) => RuleSet::load(path),
+            None => Ok(RuleSet::builtin()),
+        };
+        metrics::RULES_RELOADS.with_label_values(&[if result.is_ok() { "success" } else { "failure" }]).inc();
+
+        let rules = result?;
+        let info = rules.info().clone();
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(rules);
+        info!("Keyword rules reloaded from {}: {} rules", info.source, info.rules);
+        Ok(info)
+    }
+
+    /// Периодически проверяет время изменения и размер файла правил
+    pub fn spawn_watcher(self: Arc<Self>, interval: Duration) {
+        let Some(path) = self.path.clone() else { return };
+        tokio::spawn(async move {
+            let stamp = |path: &Path| -> Option<(u64, SystemTime)> {
+                let meta = std::fs::metadata(path).ok()?;
+                Some((meta.len(), meta.modified().ok()?))
+            };
+            let mut loaded = stamp(&path);
+            info!("Watching {:?} for rule changes every {:?}", path, interval);
+
+            loop {
+                tokio::time::sleep(interval).await;
+                let current = stamp(&path);
+                if current == loaded {
+                    continue;
+                }
+                loaded = current;
+                if let Err(e) = self.reload() {
+                    error!("Keyword rules reload failed, keeping current rules: {:#}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(yaml: &str) -> RuleSet {
+        RuleSet::parse(yaml, false, "test".to_string()).expect("valid rules")
+    }
+
+    /// Сработавшие правила: id, совпавший текст, было ли отрицание, учтено ли совпадение
+    fn fired(rules: &RuleSet, text: &str) -> Vec<(String, String, bool, bool)> {
+        rules.evaluate(text).fired.into_iter().map(|f| (f.rule, f.matched, f.negated, f.applied)).collect()
+    }
+
+    fn hit(rule: &str, matched: &str, negated: bool, applied: bool) -> (String, String, bool, bool) {
+        (rule.to_string(), matched.to_string(), negated, applied)
+    }
+
+    #[test]
+    fn builtin_rules_are_valid() {
+        assert!(!RuleSet::builtin().topics().is_empty());
+    }
+
+    #[test]
+    fn stems_match_word_starts() {
+        let rules = rules(
+            "rules:
+               - {id: mortgage, stems: [ипотек], topic: Ипотека}
+               - {id: app, stems: [мобильное прилож], topic: Приложение}",
+        );
+        assert_eq!(fired(&rules, "ИПОТЕКУ дали"), [hit("mortgage", "ИПОТЕКУ", false, true)]);
+        assert_eq!(fired(&rules, "ипотечный кредит, неипотека"), []);
+        assert_eq!(fired(&rules, "мобильное\n приложением"), [hit("app", "мобильное\n приложением", false, true)]);
+    }
+
+    #[test]
+    fn words_match_whole_words() {
+        let rules = rules("rules: [{id: card, words: [карта], topic: Карты}]");
+        assert_eq!(fired(&rules, "Карта пришла"), [hit("card", "Карта", false, true)]);
+        assert_eq!(fired(&rules, "картами не пользуюсь"), []);
+    }
+
+    #[test]
+    fn regex_matches_as_written() {
+        let rules = rules(r"rules: [{id: rate, regex: ['\d+(?:[.,]\d+)?\s*%'], topic: Кредиты}]");
+        assert_eq!(fired(&rules, "ставка 12,5 % годовых"), [hit("rate", "12,5 %", false, true)]);
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        for yaml in [
+            "rules: [{id: a, stems: [x], topic: T}, {id: a, stems: [y], topic: T}]",
+            "rules: [{id: a, stems: [x]}]",
+            "rules: [{id: a, topic: T}]",
+            "rules: [{id: a, regex: ['('], topic: T}]",
+            "rules: [{id: a, stems: [x], topic: T, unknown: 1}]",
+        ] {
+            assert!(RuleSet::parse(yaml, false, "test".to_string()).is_err(), "{yaml}");
+        }
+    }
+
+    #[test]
+    fn negation_is_checked_within_window_and_clause() {
+        let rules =
+            rules("negation: {particles: [не], window: 2}\nrules: [{id: good, stems: [хорош], sentiment: positive}]");
+        assert_eq!(fired(&rules, "не очень хорошо"), [hit("good", "хорошо", true, true)]);
+        assert_eq!(fired(&rules, "не было очень хорошо"), [hit("good", "хорошо", false, true)]);
+        assert_eq!(fired(&rules, "не знаю, хорошо"), [hit("good", "хорошо", false, true)]);
+    }
+
+    #[test]
+    fn on_negation_controls_negated_matches() {
+        let rules = rules(
+            "rules:
+               - {id: keep, stems: [кредит], topic: Кредиты}
+               - {id: skip, words: [положительно], sentiment: positive, on_negation: skip}
+               - {id: flip, stems: [понрав], sentiment: positive}",
+        );
+        let explanation = rules.evaluate("не кредит, не положительно, не понравилось");
+        let by_id = |id: &str| explanation.fired.iter().find(|f| f.rule == id).expect("rule fired");
+        assert!(by_id("keep").negated && by_id("keep").applied);
+        assert!(!by_id("skip").applied && by_id("skip").score.is_none());
+        assert_eq!(by_id("flip").sentiment, Some(Sentiment::Negative));
+        assert_eq!(explanation.topics[0].topic, "Кредиты");
+        assert_eq!(explanation.sentiment, Some(Sentiment::Negative));
+    }
+
+    static BUILTIN: Lazy<RuleSet> = Lazy::new(RuleSet::builtin);
+
+    /// Тональность и оценка всего текста по встроенным правилам
+    fn judged(text: &str) -> (Sentiment, f32) {
+        let explanation = BUILTIN.evaluate(text);
+        (explanation.sentiment.expect("sentiment rules fired"), explanation.score.expect("score"))
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+    }
+
+    /// Оценка после сжатия суммы вкладов
+    fn squashed(total: f32) -> f32 {
+        total / (total * total + 1.0).sqrt()
+    }
+
+    #[test]
+    fn score_is_squashed_sum_of_contributions() {
+        assert_close(judged("хорошо").1, squashed(1.0));
+        assert_close(judged("хорошо и удобно").1, squashed(2.0));
+        assert_close(judged("плохо").1, squashed(-1.25));
+        assert_close(judged("положительно").1, squashed(5.0));
+    }
+
+    #[test]
+    fn adjectives_match_sentiment_stems() {
+        for text in ["хорошее обслуживание", "отличная карта", "отличный сервис"] {
+            assert_eq!(judged(text).0, Sentiment::Positive, "{text}");
+        }
+        for text in ["плохая поддержка", "долгий ответ", "кредит под большой процент"] {
+            assert_eq!(judged(text).0, Sentiment::Negative, "{text}");
+        }
+        assert!(BUILTIN.evaluate("закрыл долг по кредиту").sentiment.is_none());
+    }
+
+    #[test]
+    fn negation_flips_and_weakens() {
+        assert_eq!(judged("не плохо"), (Sentiment::Positive, squashed(1.25 * 0.75)));
+        assert_eq!(judged("не долго ждал").0, Sentiment::Positive);
+        assert_eq!(judged("не понравилось"), (Sentiment::Negative, squashed(-0.75)));
+    }
+
+    #[test]
+    fn intensifiers_scale_unless_between_negation_and_word() {
+        assert_close(judged("очень хорошо").1, squashed(1.5));
+        assert_close(judged("довольно хорошо").1, squashed(0.8));
+        // «не очень хорошо»: усилитель после отрицания не учитывается
+        assert_eq!(judged("не очень хорошо"), (Sentiment::Negative, squashed(-0.75)));
+        // «совсем не понравилось»: усилитель перед отрицанием усиливает
+        assert_close(judged("совсем не понравилось").1, squashed(-0.75 * 1.5));
+    }
+
+    #[test]
+    fn contrast_weights_clauses_around_conjunction() {
+        assert_close(judged("быстро, но плохо").1, squashed(0.5 - 1.25 * 1.5));
+        assert_close(judged("плохо, зато быстро").1, squashed(-1.25 * 0.5 + 1.5));
+        // Противопоставление не выходит за предложение
+        assert_close(judged("Быстро. Но плохо").1, squashed(1.0 - 1.25 * 1.5));
+    }
+
+    #[test]
+    fn small_scores_are_neutral() {
+        let rules = rules(
+            "scoring: {neutral_threshold: 0.6, intensifiers: {немного: 0.6}}
+rules: [{id: good, stems: [хорош], sentiment: positive}]",
+        );
+        assert_eq!(rules.evaluate("хорошо").sentiment, Some(Sentiment::Positive));
+        assert_eq!(rules.evaluate("немного хорошо").sentiment, Some(Sentiment::Neutral));
+        assert_eq!(rules.evaluate("кредит").sentiment, None);
+    }
+
+    #[test]
+    fn topics_follow_priority_then_file_order() {
+        let rules = rules(
+            "rules:
+               - {id: low, stems: [карт], topic: Карты, priority: -2147483648}
+               - {id: first, stems: [вклад], topic: Вклады}
+               - {id: second, stems: [кредит], topic: Кредиты}
+               - {id: high, stems: [ипотек], topic: Ипотека, priority: 2147483647}",
+        );
+        let topics: Vec<String> =
+            rules.evaluate("карта, кредит, вклад, ипотека").topics.into_iter().map(|t| t.topic).collect();
+        assert_eq!(topics, ["Ипотека", "Вклады", "Кредиты", "Карты"]);
+    }
+
+    #[test]
+    fn mixed_review_scores_each_topic() {
+        let topics = BUILTIN.evaluate("Обслуживание хорошее, но кредит дали под большой процент").topics;
+        let labels: Vec<(&str, Sentiment)> = topics.iter().map(|t| (t.topic.as_str(), t.sentiment)).collect();
+        assert_eq!(labels, [("Обслуживание", Sentiment::Positive), ("Кредиты", Sentiment::Negative)]);
+    }
+
+    /// Тональность и фрагменты каждого топика по встроенным правилам
+    fn aspects(text: &str) -> Vec<(String, Sentiment, Vec<String>)> {
+        BUILTIN
+            .evaluate(text)
+            .topics
+            .into_iter()
+            .map(|t| (t.topic, t.sentiment, t.spans.into_iter().map(|s| s.text).collect()))
+            .collect()
+    }
+
+    fn aspect(topic: &str, sentiment: Sentiment, spans: &[&str]) -> (String, Sentiment, Vec<String>) {
+        (topic.to_string(), sentiment, spans.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn contrast_separates_topics() {
+        assert_eq!(
+            aspects("ипотеку одобрили быстро, но приложение зависает"),
+            [
+                aspect("Мобильное приложение", Sentiment::Negative, &["но приложение зависает"]),
+                aspect("Ипотека", Sentiment::Positive, &["ипотеку одобрили быстро"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn clause_after_contrast_is_not_given_to_earlier_topic() {
+        assert_eq!(aspects("Ипотеку одобрили, но долго"), [aspect("Ипотека", Sentiment::Neutral, &["Ипотеку одобрили"])]);
+        assert_eq!(
+            aspects("Быстро, но ипотеку не одобрили, карта удобная"),
+            [
+                aspect("Ипотека", Sentiment::Neutral, &["но ипотеку не одобрили"]),
+                aspect("Карты", Sentiment::Positive, &["карта удобная"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn clauses_without_topic_follow_nearest_topic() {
+        assert_eq!(
+            aspects("Быстро и удобно, карту выдали, кредит тоже"),
+            [
+                aspect("Кредиты", Sentiment::Neutral, &["кредит тоже"]),
+                aspect("Карты", Sentiment::Positive, &["Быстро и удобно, карту выдали"]),
+            ]
+        );
+        // Предложение без топиков относится ко всем
+        assert_eq!(aspects("Кредит дали. Всё быстро"), [aspect("Кредиты", Sentiment::Positive, &["Кредит дали", "Всё быстро"])]);
+    }
+
+    #[test]
+    fn spans_use_char_offsets() {
+        let topics = BUILTIN.evaluate("Карта ок. Приложение зависает").topics;
+        let app = topics.iter().find(|t| t.topic == "Мобильное приложение").expect("app topic");
+        assert_eq!((app.spans[0].start, app.spans[0].end), (10, 29));
+    }
+
+    #[test]
+    fn unmentioned_topics_get_whole_text_sentiment() {
+        let topics = BUILTIN.aspects("карта удобная, но приложение зависает", &["Вклады".to_string()]);
+        assert_eq!(topics[0].sentiment, Sentiment::Negative);
+        assert!(topics[0].spans.is_empty());
+    }
+
+    #[derive(Deserialize)]
+    struct Case {
+        text: String,
+        topics: Vec<String>,
+        sentiments: Vec<String>,
+    }
+
+    /// Те же примеры проверяет ai_model/test_simple_predict.py: бэкенд и serve.py отвечают одинаково
+    #[test]
+    fn matches_python_predictor_on_shared_cases() {
+        let cases: Vec<Case> = serde_json::from_str(include_str!("rules_cases.json")).expect("valid cases");
+        for case in cases {
+            let expected: Vec<(String, Option<Sentiment>)> =
+                case.topics.into_iter().zip(case.sentiments.iter().map(|s| Sentiment::from_label(s))).collect();
+            let actual: Vec<(String, Option<Sentiment>)> =
+                BUILTIN.evaluate(&case.text).topics.into_iter().map(|t| (t.topic, Some(t.sentiment))).collect();
+            assert_eq!(actual, expected, "{}", case.text);
+        }
+    }
+
+    #[test]
+    fn json_rules_are_accepted() {
+        let json = r#"{"rules": [{"id": "card", "stems": ["карт"], "topic": "Карты"}]}"#;
+        let rules = RuleSet::parse(json, true, "test".to_string()).expect("valid rules");
+        assert_eq!(rules.topics(), ["Карты"]);
+    }
+}
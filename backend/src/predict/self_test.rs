@@ -0,0 +1,60 @@
+use anyhow::{bail, Context, Result};
+use tracing::{info, warn};
+
+use crate::domain::{PredictSample, Sentiment};
+use crate::predict::registry::ModelRegistry;
+use crate::predict::Predictor;
+
+/// Эталонные тексты и ожидаемая тональность
+const GOLDEN_TEXTS: [(&str, Sentiment); 3] = [
+    ("Ипотеку одобрили быстро, менеджер всё отлично объяснил", Sentiment::Positive),
+    ("Мобильное приложение постоянно зависает, поддержка отвечает долго", Sentiment::Negative),
+    ("Оформил кредитную карту в отделении", Sentiment::Neutral),
+];
+
+/// Прогоняет эталонные тексты через предиктор при старте
+///
+/// Ошибкой считаются только нарушения формата ответа (нет ответа, пустые топики,
+/// уверенность вне [0, 1]); расхождение с ожидаемой тональностью лишь логируется.
+pub async fn run(predictor: &dyn Predictor) -> Result<()> {
+    let samples: Vec<PredictSample> = GOLDEN_TEXTS
+        .iter()
+        .enumerate()
+        .map(|(i, (text, _))| PredictSample { id: i as i64 + 1, text: text.to_string(), date: None, region: None })
+        .collect();
+
+    let output = predictor.predict(&samples).await?;
+    if let Some((id, e)) = output.errors.first() {
+        bail!("golden sample {} failed: {}", id, e);
+    }
+
+    for (sample, (_, expected)) in samples.iter().zip(GOLDEN_TEXTS) {
+        let matches: Vec<_> = output.items.iter().filter(|p| p.id == sample.id).collect();
+        let [item] = matches.as_slice() else {
+            bail!("golden sample {} got {} predictions, expected exactly one", sample.id, matches.len());
+        };
+        if item.topics.is_empty() {
+            bail!("golden sample {}: no topics", sample.id);
+        }
+        if let Some(invalid) = item.topics.iter().find(|t| t.score.is_some_and(|s| !(0.0..=1.0).contains(&s))) {
+            bail!("golden sample {}: topic {:?} has score {:?} outside [0, 1]", sample.id, invalid.topic, invalid.score);
+        }
+
+        let labels: Vec<_> = item.topics.iter().map(|t| (t.topic.as_str(), t.sentiment.as_str())).collect();
+        info!("Self-test {:?} -> {:?}", sample.text, labels);
+        if !item.topics.iter().any(|t| t.sentiment == expected) {
+            warn!("Self-test {:?}: expected {} sentiment", sample.text, expected.as_str());
+        }
+    }
+
+    info!("Predictor self-test passed on {} golden texts", samples.len());
+    Ok(())
+}
+
+/// Самопроверка каждой версии реестра
+pub async fn run_registry(registry: &ModelRegistry) -> Result<()> {
+    for (name, predictor) in registry.iter() {
+        run(predictor.as_ref()).await.with_context(|| format!("model {}", name))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::domain::{LabelAgreement, ModelInfo, PredictItem, PredictSample, Sentiment, ShadowDisagreement, ShadowStatsItem};
+use crate::metrics;
+use crate::predict::{PredictError, PredictOutput, Predictor};
+
+/// Сколько последних расхождений хранится для разбора
+const DISAGREEMENTS_KEPT: usize = 200;
+
+/// Отвечает результатом основного предиктора и асинхронно прогоняет те же отзывы
+/// через теневые, сравнивая топики и тональности
+pub struct ShadowPredictor {
+    primary: Arc<dyn Predictor>,
+    shadows: Vec<(String, Arc<dyn Predictor>)>,
+    /// Ограничение фоновых теневых вызовов; при насыщении сравнение пропускается
+    in_flight: Arc<Semaphore>,
+    stats: Arc<ShadowStats>,
+}
+
+impl ShadowPredictor {
+    pub fn new(primary: Arc<dyn Predictor>, shadows: Vec<(String, Arc<dyn Predictor>)>, max_in_flight: usize) -> Self {
+        Self {
+            primary,
+            shadows,
+            in_flight: Arc::new(Semaphore::new(max_in_flight.max(1))),
+            stats: Arc::new(ShadowStats::default()),
+        }
+    }
+
+    pub fn stats(&self) -> &Arc<ShadowStats> {
+        &self.stats
+    }
+}
+
+#[async_trait]
+impl Predictor for ShadowPredictor {
+    async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        let result = self.primary.predict(samples).await;
+        let Ok(output) = &result else { return result };
+        if output.items.is_empty() {
+            return result;
+        }
+
+        // Сравниваем только отзывы, на которые основной предиктор ответил
+        let primary: Arc<HashMap<i64, PredictItem>> = Arc::new(output.items.iter().map(|p| (p.id, p.clone())).collect());
+        let samples: Arc<Vec<PredictSample>> =
+            Arc::new(samples.iter().filter(|s| primary.contains_key(&s.id)).cloned().collect());
+
+        for (name, shadow) in &self.shadows {
+            let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+                self.stats.record_skipped(name, samples.len());
+                continue;
+            };
+            let (name, shadow, stats) = (name.clone(), shadow.clone(), self.stats.clone());
+            let (primary, samples) = (primary.clone(), samples.clone());
+            tokio::spawn(async move {
+                let _permit = permit;
+                match shadow.predict(&samples).await {
+                    Ok(output) => stats.record(&name, &samples, &primary, output),
+                    Err(e) => {
+                        warn!("Shadow predictor {} failed: {}", name, e);
+                        stats.record_failed(&name, samples.len());
+                    }
+                }
+            });
+        }
+        result
+    }
+
+    fn describe(&self) -> ModelInfo {
+        ModelInfo {
+            shadows: self
+                .shadows
+                .iter()
+                .map(|(name, shadow)| ModelInfo { version: Some(name.clone()), ..shadow.describe() })
+                .collect(),
+            ..self.primary.describe()
+        }
+    }
+
+    async fn check_ready(&self) -> Result<(), PredictError> {
+        self.primary.check_ready().await
+    }
+}
+
+/// Накопленная согласованность теневых предикторов с основным
+#[derive(Default)]
+pub struct ShadowStats {
+    shadows: Mutex<BTreeMap<String, ShadowStatsItem>>,
+    disagreements: Mutex<VecDeque<ShadowDisagreement>>,
+}
+
+impl ShadowStats {
+    pub fn snapshot(&self) -> Vec<ShadowStatsItem> {
+        self.shadows.lock().unwrap_or_else(|e| e.into_inner()).values().cloned().collect()
+    }
+
+    /// Последние расхождения, новые первыми
+    pub fn disagreements(&self, shadow: Option<&str>, limit: usize) -> Vec<ShadowDisagreement> {
+        self.disagreements
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .rev()
+            .filter(|d| shadow.is_none_or(|name| d.shadow == name))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    fn with_item(&self, name: &str, f: impl FnOnce(&mut ShadowStatsItem)) {
+        let mut shadows = self.shadows.lock().unwrap_or_else(|e| e.into_inner());
+        let item = shadows
+            .entry(name.to_string())
+            .or_insert_with(|| ShadowStatsItem { shadow: name.to_string(), ..ShadowStatsItem::default() });
+        f(item);
+    }
+
+    fn record_skipped(&self, name: &str, samples: usize) {
+        self.with_item(name, |item| item.skipped += samples as u64);
+    }
+
+    fn record_failed(&self, name: &str, samples: usize) {
+        self.with_item(name, |item| item.failed += samples as u64);
+    }
+
+    fn record(&self, name: &str, samples: &[PredictSample], primary: &HashMap<i64, PredictItem>, output: PredictOutput) {
+        let mut answers: HashMap<i64, PredictItem> = output.items.into_iter().map(|p| (p.id, p)).collect();
+        let mut disagreements = Vec::new();
+
+        self.with_item(name, |item| {
+            item.failed += output.errors.len() as u64;
+            for sample in samples {
+                let (Some(expected), Some(actual)) = (primary.get(&sample.id), answers.remove(&sample.id)) else {
+                    continue;
+                };
+                item.compared += 1;
+                if compare(name, item, expected, &actual) {
+                    item.exact_matches += 1;
+                } else {
+                    disagreements.push(ShadowDisagreement {
+                        at: chrono::Utc::now(),
+                        shadow: name.to_string(),
+                        id: sample.id,
+                        text: sample.text.clone(),
+                        primary: expected.clone(),
+                        candidate: actual,
+                    });
+                }
+            }
+            item.agreement_rate = rate(item.exact_matches, item.compared);
+        });
+
+        if !disagreements.is_empty() {
+            let mut kept = self.disagreements.lock().unwrap_or_else(|e| e.into_inner());
+            kept.extend(disagreements);
+            while kept.len() > DISAGREEMENTS_KEPT {
+                kept.pop_front();
+            }
+        }
+    }
+}
+
+/// Учитывает согласие по каждому топику и по тональности общих топиков; true — полное совпадение
+fn compare(name: &str, item: &mut ShadowStatsItem, expected: &PredictItem, actual: &PredictItem) -> bool {
+    let pairs = |p: &PredictItem| -> HashMap<String, Sentiment> {
+        p.topics.iter().map(|t| (t.topic.clone(), t.sentiment)).collect()
+    };
+    let (expected, actual) = (pairs(expected), pairs(actual));
+    let mut exact = true;
+
+    let topics: HashSet<&String> = expected.keys().chain(actual.keys()).collect();
+    for topic in topics {
+        let agree = expected.contains_key(topic) && actual.contains_key(topic);
+        exact &= agree;
+        count(name, "topic", &mut item.topics, topic, agree);
+    }
+    for (topic, sentiment) in &expected {
+        if let Some(other) = actual.get(topic) {
+            let agree = sentiment == other;
+            exact &= agree;
+            count(name, "sentiment", &mut item.sentiments, sentiment.as_str(), agree);
+        }
+    }
+    exact
+}
+
+fn count(name: &str, dimension: &str, labels: &mut BTreeMap<String, LabelAgreement>, label: &str, agree: bool) {
+    let entry = labels.entry(label.to_string()).or_default();
+    if agree {
+        entry.agree += 1;
+    } else {
+        entry.disagree += 1;
+    }
+    entry.rate = rate(entry.agree, entry.agree + entry.disagree);
+    metrics::SHADOW_COMPARISONS
+        .with_label_values(&[name, dimension, label, if agree { "agree" } else { "disagree" }])
+        .inc();
+}
+
+fn rate(part: u64, total: u64) -> f64 {
+    if total == 0 { 0.0 } else { part as f64 / total as f64 }
+}
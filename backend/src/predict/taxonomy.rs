@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::domain::{ModelInfo, PredictItem, PredictSample, TopicScore, TopicSentiment};
+use crate::metrics;
+use crate::predict::{PredictError, PredictOutput, Predictor};
+use crate::taxonomy::{SharedTaxonomy, Taxonomy};
+
+/// Приводит метки предиктора к таксономии: синоним заменяется именем топика и его id,
+/// топики неактивных веток отбрасываются, неизвестные метки проходят как есть
+pub struct TaxonomyPredictor {
+    inner: Arc<dyn Predictor>,
+    taxonomy: Arc<SharedTaxonomy>,
+}
+
+impl TaxonomyPredictor {
+    pub fn new(inner: Arc<dyn Predictor>, taxonomy: Arc<SharedTaxonomy>) -> Self {
+        Self { inner, taxonomy }
+    }
+}
+
+/// Имя топика для метки; `None` — топик деактивирован
+fn canonical(taxonomy: &Taxonomy, label: &str) -> Option<(String, Option<i32>)> {
+    match taxonomy.resolve(label) {
+        Some(topic) if topic.active => Some((topic.name.clone(), Some(topic.id))),
+        Some(_) => {
+            metrics::UNRESOLVED_TOPICS.with_label_values(&[label, "inactive"]).inc();
+            None
+        }
+        None => {
+            metrics::UNRESOLVED_TOPICS.with_label_values(&[label, "unknown"]).inc();
+            Some((label.to_string(), None))
+        }
+    }
+}
+
+fn resolve_item(taxonomy: &Taxonomy, item: &mut PredictItem) {
+    let mut topics: Vec<TopicSentiment> = Vec::with_capacity(item.topics.len());
+    for mut label in std::mem::take(&mut item.topics) {
+        let Some((name, topic_id)) = canonical(taxonomy, &label.topic) else { continue };
+        // Две метки одного топика (`Карта` и `Карты`) — остаётся первая с большей уверенностью
+        match topics.iter_mut().find(|t| t.topic == name) {
+            Some(existing) => {
+                if let Some(score) = label.score
+                    && existing.score.is_none_or(|s| s < score)
+                {
+                    existing.score = Some(score);
+                }
+            }
+            None => {
+                label.topic = name;
+                label.topic_id = topic_id;
+                topics.push(label);
+            }
+        }
+    }
+    item.topics = topics;
+
+    let mut rejected: Vec<TopicScore> = Vec::with_capacity(item.rejected_topics.len());
+    for candidate in std::mem::take(&mut item.rejected_topics) {
+        let Some((name, _)) = canonical(taxonomy, &candidate.topic) else { continue };
+        if !item.topics.iter().any(|t| t.topic == name) && !rejected.iter().any(|r| r.topic == name) {
+            rejected.push(TopicScore { topic: name, score: candidate.score });
+        }
+    }
+    item.rejected_topics = rejected;
+}
+
+#[async_trait]
+impl Predictor for TaxonomyPredictor {
+    async fn predict(&self, samples: &[PredictSample]) -> Result<PredictOutput, PredictError> {
+        let mut output = self.inner.predict(samples).await?;
+        let taxonomy = self.taxonomy.current();
+        for item in &mut output.items {
+            resolve_item(&taxonomy, item);
+        }
+        Ok(output)
+    }
+
+    fn describe(&self) -> ModelInfo {
+        let info = self.inner.describe();
+        let taxonomy = self.taxonomy.current();
+        let mut topic_labels: Vec<String> = Vec::with_capacity(info.topic_labels.len());
+        for label in &info.topic_labels {
+            let name = taxonomy.resolve(label).map_or_else(|| label.clone(), |topic| topic.name.clone());
+            if !topic_labels.contains(&name) {
+                topic_labels.push(name);
+            }
+        }
+        ModelInfo { topic_labels, ..info }
+    }
+
+    async fn check_ready(&self) -> Result<(), PredictError> {
+        self.inner.check_ready().await
+    }
+}
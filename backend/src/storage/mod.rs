@@ -0,0 +1,658 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, bail, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use tracing::info;
+
+use crate::domain::{ReviewItem, Sentiment, SentimentStats, TimelinePoint, Topic, TopicInput, TopicsStatsItem};
+use crate::taxonomy::{Taxonomy, TaxonomyError};
+
+/// Версия схемы в `PRAGMA user_version`
+const SCHEMA_VERSION: i32 = 3;
+
+/// Начальная таксономия: имя, родитель и синонимы, под которыми топик называют модели.
+/// В существующих базах топики сопоставляются по имени: их id могут отличаться от порядка в списке.
+const SEED_TOPICS: [(&str, Option<&str>, &[&str]); 11] = [
+    ("Ипотека", Some("Кредиты"), &[]),
+    ("Карты", None, &["Карта", "Кредитная карта", "Дебетовая карта"]),
+    ("Кредиты", None, &["Кредит"]),
+    ("Вклады", None, &["Вклад", "Депозит"]),
+    ("Обслуживание", None, &["Отделение"]),
+    ("Дистанционное обслуживание", None, &[]),
+    ("Мобильное приложение", Some("Дистанционное обслуживание"), &["Приложение"]),
+    ("Онлайн-банк", Some("Дистанционное обслуживание"), &["Интернет-банк"]),
+    ("Сайт", Some("Дистанционное обслуживание"), &[]),
+    ("Поддержка", Some("Обслуживание"), &["Служба поддержки"]),
+    ("Терминал", Some("Обслуживание"), &["Банкомат"]),
+];
+
+/// Отзыв с результатами предсказания, готовый к сохранению
+pub struct NewReview {
+    pub id: i64,
+    pub date: String,
+    pub region: String,
+    pub text: String,
+    pub topics: Vec<(String, Sentiment)>,
+}
+
+/// Фильтры выборки отзывов (`/reviews`)
+pub struct ReviewFilter {
+    pub topic_id: Option<i32>,
+    pub sentiment: Option<Sentiment>,
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub region: Option<String>,
+    pub page: i64,
+    pub limit: i64,
+}
+
+/// Хранилище отзывов на SQLite
+pub struct ReviewStore {
+    conn: Mutex<Connection>,
+}
+
+/// Тональность отзыва целиком: отрицательная имеет приоритет, затем положительная
+const REVIEW_SENTIMENT_SQL: &str = "CASE \
+    WHEN SUM(rt.sentiment = 'negative') > 0 THEN 'negative' \
+    WHEN SUM(rt.sentiment = 'positive') > 0 THEN 'positive' \
+    ELSE 'neutral' END";
+
+impl ReviewStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create database directory {:?}", parent))?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("failed to open database {:?}", path))?;
+        let store = Self::from_connection(conn)?;
+        info!("Review store opened at {:?}", path);
+        Ok(store)
+    }
+
+    /// Хранилище поверх открытого соединения; схема мигрируется до текущей версии
+    fn from_connection(conn: Connection) -> Result<Self> {
+        let store = Self { conn: Mutex::new(conn) };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    fn conn(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn.lock().map_err(|_| anyhow!("review store mutex poisoned"))
+    }
+
+    fn migrate(&self) -> Result<()> {
+        let conn = self.conn()?;
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+             PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS topics (
+                 id   INTEGER PRIMARY KEY AUTOINCREMENT,
+                 name TEXT NOT NULL UNIQUE
+             );
+             CREATE TABLE IF NOT EXISTS reviews (
+                 id     INTEGER PRIMARY KEY,
+                 date   TEXT NOT NULL,
+                 region TEXT NOT NULL DEFAULT '',
+                 text   TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS review_topics (
+                 review_id INTEGER NOT NULL REFERENCES reviews(id) ON DELETE CASCADE,
+                 topic_id  INTEGER NOT NULL REFERENCES topics(id),
+                 sentiment TEXT NOT NULL,
+                 PRIMARY KEY (review_id, topic_id)
+             );
+             CREATE TABLE IF NOT EXISTS uploads (
+                 id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                 file_name  TEXT NOT NULL,
+                 created_at TEXT NOT NULL,
+                 payload    TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS idx_reviews_date ON reviews(date);
+             CREATE INDEX IF NOT EXISTS idx_review_topics_topic ON review_topics(topic_id);",
+        )?;
+
+        let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version < SCHEMA_VERSION {
+            let tx = conn.unchecked_transaction()?;
+            if version < 1 {
+                Self::migrate_taxonomy(&tx)?;
+            }
+            if version < 2 {
+                Self::migrate_credit_cards(&tx)?;
+            }
+            if version < 3 {
+                Self::migrate_review_keys(&tx)?;
+            }
+            tx.execute_batch(&format!("PRAGMA user_version = {SCHEMA_VERSION}"))?;
+            tx.commit()?;
+            info!("Review store migrated to schema version {}", SCHEMA_VERSION);
+        }
+        Ok(())
+    }
+
+    /// Иерархия, флаг активности и синонимы топиков; заполняет начальную таксономию
+    ///
+    /// Топики, которые раньше создавались по меткам моделей (`Карта`), становятся синонимами
+    /// топиков таксономии, а их отзывы переносятся к основному топику.
+    fn migrate_taxonomy(tx: &Transaction<'_>) -> Result<()> {
+        tx.execute_batch(
+            "ALTER TABLE topics ADD COLUMN parent_id INTEGER REFERENCES topics(id);
+             ALTER TABLE topics ADD COLUMN active INTEGER NOT NULL DEFAULT 1;
+             CREATE TABLE IF NOT EXISTS topic_synonyms (
+                 topic_id INTEGER NOT NULL REFERENCES topics(id) ON DELETE CASCADE,
+                 synonym  TEXT NOT NULL UNIQUE
+             );
+             CREATE INDEX IF NOT EXISTS idx_topics_parent ON topics(parent_id);",
+        )?;
+
+        for (name, _, _) in SEED_TOPICS {
+            tx.execute(
+                "INSERT INTO topics (name) SELECT ?1 WHERE NOT EXISTS (SELECT 1 FROM topics WHERE name = ?1)",
+                params![name],
+            )?;
+        }
+        let id_of = |name: &str| -> Result<Option<i64>> {
+            Ok(tx.query_row("SELECT id FROM topics WHERE name = ?1", params![name], |row| row.get(0)).optional()?)
+        };
+        for (name, parent, synonyms) in SEED_TOPICS {
+            let id = id_of(name)?.context("seed topic is missing")?;
+            if let Some(parent) = parent {
+                tx.execute("UPDATE topics SET parent_id = ?1 WHERE id = ?2", params![id_of(parent)?, id])?;
+            }
+            for synonym in synonyms {
+                if let Some(old) = id_of(synonym)? {
+                    tx.execute("UPDATE OR IGNORE review_topics SET topic_id = ?1 WHERE topic_id = ?2", params![id, old])?;
+                    tx.execute("DELETE FROM review_topics WHERE topic_id = ?1", params![old])?;
+                    tx.execute("DELETE FROM topics WHERE id = ?1", params![old])?;
+                }
+                tx.execute("INSERT OR IGNORE INTO topic_synonyms (topic_id, synonym) VALUES (?1, ?2)", params![id, synonym])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Проверка соединения для `/readyz`
+    pub fn ping(&self) -> Result<()> {
+        self.conn()?.query_row("SELECT 1", [], |_| Ok(()))?;
+        Ok(())
+    }
+
+    /// Сохраняет загруженный файл: отзывы с предсказаниями и обогащённый JSON для `/download`
+    pub fn save_upload(&self, file_name: &str, reviews: &[NewReview], payload: &str) -> Result<i64> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO uploads (file_name, created_at, payload) VALUES (?1, ?2, ?3)",
+            params![file_name, chrono::Utc::now().to_rfc3339(), payload],
+        )?;
+        let upload_id = tx.last_insert_rowid();
+        Self::insert_reviews(&tx, upload_id, reviews)?;
+        tx.commit()?;
+        Ok(upload_id)
+    }
+
+    /// Обогащённый JSON загрузки (по умолчанию последней): имя файла и содержимое
+    pub fn upload_payload(&self, upload_id: Option<i64>) -> Result<Option<(String, String)>> {
+        let conn = self.conn()?;
+        let row = conn
+            .query_row(
+                "SELECT file_name, payload FROM uploads
+                 WHERE ?1 IS NULL OR id = ?1
+                 ORDER BY id DESC LIMIT 1",
+                params![upload_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(row)
+    }
+
+    /// Сохраняет отзывы загрузки; id из файла хранится в `external_id` и уникален только
+    /// в пределах загрузки, поэтому отзывы разных загрузок не перезаписывают друг друга
+    ///
+    /// Метка находится по имени или синониму топика; неизвестная метка становится новым топиком.
+    fn insert_reviews(tx: &Transaction<'_>, upload_id: i64, reviews: &[NewReview]) -> Result<()> {
+        let mut insert_review = tx.prepare(
+            "INSERT INTO reviews (upload_id, external_id, date, region, text) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        let mut insert_new_topic = tx.prepare("INSERT INTO topics (name) VALUES (?1)")?;
+        let mut topic_id = tx.prepare(
+            "SELECT id FROM topics WHERE name = ?1
+             UNION ALL SELECT topic_id FROM topic_synonyms WHERE synonym = ?1
+             LIMIT 1",
+        )?;
+        let mut insert_topic = tx.prepare(
+            "INSERT OR REPLACE INTO review_topics (review_id, topic_id, sentiment) VALUES (?1, ?2, ?3)",
+        )?;
+
+        for review in reviews {
+            insert_review.execute(params![upload_id, review.id, review.date, review.region, review.text])?;
+            let review_id = tx.last_insert_rowid();
+            for (topic, sentiment) in &review.topics {
+                let id: i64 = match topic_id.query_row(params![topic], |row| row.get(0)).optional()? {
+                    Some(id) => id,
+                    None => {
+                        insert_new_topic.execute(params![topic])?;
+                        tx.last_insert_rowid()
+                    }
+                };
+                insert_topic.execute(params![review_id, id, sentiment.as_str()])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Кредитные карты выделяются в дочерний топик Карт вместе с синонимом `Кредитная карта`
+    fn migrate_credit_cards(tx: &Transaction<'_>) -> Result<()> {
+        let cards: Option<i64> =
+            tx.query_row("SELECT id FROM topics WHERE name = 'Карты'", [], |row| row.get(0)).optional()?;
+        // Таксономию уже изменили через API — не вмешиваемся
+        let Some(cards) = cards else { return Ok(()) };
+        tx.execute(
+            "INSERT INTO topics (name, parent_id) SELECT 'Кредитные карты', ?1
+             WHERE NOT EXISTS (SELECT 1 FROM topics WHERE name = 'Кредитные карты')",
+            params![cards],
+        )?;
+        let credit_cards: i64 = tx.query_row("SELECT id FROM topics WHERE name = 'Кредитные карты'", [], |row| row.get(0))?;
+        tx.execute(
+            "UPDATE topic_synonyms SET topic_id = ?1 WHERE synonym = 'Кредитная карта' AND topic_id = ?2",
+            params![credit_cards, cards],
+        )?;
+        tx.execute("INSERT OR IGNORE INTO topic_synonyms (topic_id, synonym) VALUES (?1, 'Кредитка')", params![credit_cards])?;
+        Ok(())
+    }
+
+    /// Суррогатный ключ отзывов: прежний id становится `external_id`, загрузка старых отзывов неизвестна
+    fn migrate_review_keys(tx: &Transaction<'_>) -> Result<()> {
+        tx.execute_batch(
+            "ALTER TABLE reviews ADD COLUMN upload_id INTEGER REFERENCES uploads(id);
+             ALTER TABLE reviews ADD COLUMN external_id INTEGER;
+             UPDATE reviews SET external_id = id;
+             CREATE INDEX IF NOT EXISTS idx_reviews_upload ON reviews(upload_id);",
+        )?;
+        Ok(())
+    }
+
+    /// Вся таксономия топиков
+    pub fn taxonomy(&self) -> Result<Taxonomy> {
+        let conn = self.conn()?;
+        Self::load_taxonomy(&conn)
+    }
+
+    fn load_taxonomy(conn: &Connection) -> Result<Taxonomy> {
+        let mut stmt = conn.prepare("SELECT topic_id, synonym FROM topic_synonyms ORDER BY rowid")?;
+        let mut synonyms: HashMap<i32, Vec<String>> = HashMap::new();
+        for row in stmt.query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))? {
+            let (topic_id, synonym) = row?;
+            synonyms.entry(topic_id).or_default().push(synonym);
+        }
+
+        let mut stmt = conn.prepare("SELECT id, name, parent_id, active FROM topics ORDER BY id")?;
+        let topics = stmt
+            .query_map([], |row| {
+                let id = row.get(0)?;
+                Ok(Topic {
+                    id,
+                    name: row.get(1)?,
+                    parent_id: row.get(2)?,
+                    synonyms: synonyms.remove(&id).unwrap_or_default(),
+                    active: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(Taxonomy::new(topics))
+    }
+
+    /// Создаёт топик; ошибки проверки возвращаются как `TaxonomyError`
+    pub fn create_topic(&self, input: &TopicInput) -> Result<Topic> {
+        let mut conn = self.conn()?;
+        let input = Self::load_taxonomy(&conn)?.validate(None, input)?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO topics (name, parent_id, active) VALUES (?1, ?2, ?3)",
+            params![input.name, input.parent_id, input.active],
+        )?;
+        let id = tx.last_insert_rowid() as i32;
+        Self::replace_synonyms(&tx, id, &input.synonyms)?;
+        tx.commit()?;
+        Ok(Topic { id, name: input.name, parent_id: input.parent_id, synonyms: input.synonyms, active: input.active })
+    }
+
+    /// Заменяет имя, родителя, синонимы и активность топика
+    pub fn update_topic(&self, id: i32, input: &TopicInput) -> Result<Topic> {
+        let mut conn = self.conn()?;
+        let taxonomy = Self::load_taxonomy(&conn)?;
+        if taxonomy.get(id).is_none() {
+            return Err(TaxonomyError::NotFound(id).into());
+        }
+        let input = taxonomy.validate(Some(id), input)?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE topics SET name = ?1, parent_id = ?2, active = ?3 WHERE id = ?4",
+            params![input.name, input.parent_id, input.active, id],
+        )?;
+        Self::replace_synonyms(&tx, id, &input.synonyms)?;
+        tx.commit()?;
+        Ok(Topic { id, name: input.name, parent_id: input.parent_id, synonyms: input.synonyms, active: input.active })
+    }
+
+    /// Удаляет топик без дочерних топиков и отзывов; иначе его нужно деактивировать
+    pub fn delete_topic(&self, id: i32) -> Result<()> {
+        let conn = self.conn()?;
+        if Self::load_taxonomy(&conn)?.get(id).is_none() {
+            return Err(TaxonomyError::NotFound(id).into());
+        }
+        let children: i64 = conn.query_row("SELECT COUNT(*) FROM topics WHERE parent_id = ?1", params![id], |row| row.get(0))?;
+        if children > 0 {
+            return Err(TaxonomyError::Conflict(format!("topic {} has {} child topics", id, children)).into());
+        }
+        let reviews: i64 =
+            conn.query_row("SELECT COUNT(*) FROM review_topics WHERE topic_id = ?1", params![id], |row| row.get(0))?;
+        if reviews > 0 {
+            return Err(TaxonomyError::Conflict(format!("topic {} is used by {} reviews, deactivate it instead", id, reviews)).into());
+        }
+        conn.execute("DELETE FROM topics WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    fn replace_synonyms(tx: &Transaction<'_>, id: i32, synonyms: &[String]) -> Result<()> {
+        tx.execute("DELETE FROM topic_synonyms WHERE topic_id = ?1", params![id])?;
+        let mut insert = tx.prepare("INSERT INTO topic_synonyms (topic_id, synonym) VALUES (?1, ?2)")?;
+        for synonym in synonyms {
+            insert.execute(params![id, synonym])?;
+        }
+        Ok(())
+    }
+
+    /// Распределение тональностей по топикам за период
+    pub fn topic_stats(&self, date_from: &str, date_to: &str, region: Option<&str>) -> Result<Vec<TopicsStatsItem>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.name,
+                    SUM(rt.sentiment = 'positive'),
+                    SUM(rt.sentiment = 'neutral'),
+                    SUM(rt.sentiment = 'negative')
+             FROM topics t
+             JOIN review_topics rt ON rt.topic_id = t.id
+             JOIN reviews r ON r.id = rt.review_id
+             WHERE r.date >= ?1 AND r.date <= ?2 AND (?3 IS NULL OR r.region = ?3)
+             GROUP BY t.id, t.name
+             ORDER BY t.id",
+        )?;
+        let items = stmt
+            .query_map(params![date_from, date_to, region], |row| {
+                Ok(TopicsStatsItem {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    stats: SentimentStats { positive: row.get(2)?, neutral: row.get(3)?, negative: row.get(4)? },
+                    children: Vec::new(),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(items)
+    }
+
+    /// Пары «отзыв — топик» за период с тональностью, для агрегации по дереву топиков
+    pub fn topic_review_sentiments(
+        &self,
+        date_from: &str,
+        date_to: &str,
+        region: Option<&str>,
+    ) -> Result<Vec<(i32, i64, Sentiment)>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT rt.topic_id, rt.review_id, rt.sentiment
+             FROM review_topics rt
+             JOIN reviews r ON r.id = rt.review_id
+             WHERE r.date >= ?1 AND r.date <= ?2 AND (?3 IS NULL OR r.region = ?3)",
+        )?;
+        let rows = stmt
+            .query_map(params![date_from, date_to, region], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get::<_, String>(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(topic_id, review_id, sentiment)| Some((topic_id, review_id, Sentiment::from_label(&sentiment)?)))
+            .collect())
+    }
+
+    /// Динамика тональностей топиков, сгруппированная по дням/неделям/месяцам
+    ///
+    /// Отзыв с несколькими топиками из `topic_ids` учитывается один раз с тональностью
+    /// отзыва целиком по этим топикам.
+    pub fn timeline(
+        &self,
+        topic_ids: &[i32],
+        date_from: &str,
+        date_to: &str,
+        group_by: &str,
+        region: Option<&str>,
+    ) -> Result<Vec<TimelinePoint>> {
+        let bucket = match group_by {
+            "day" => "r.date",
+            // Понедельник недели
+            "week" => "date(r.date, 'weekday 0', '-6 days')",
+            "month" => "strftime('%Y-%m-01', r.date)",
+            other => bail!("unsupported group_by {:?}, expected day, week or month", other),
+        };
+
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT bucket,
+                    SUM(review_sentiment = 'positive'),
+                    SUM(review_sentiment = 'neutral'),
+                    SUM(review_sentiment = 'negative')
+             FROM (
+                 SELECT {bucket} AS bucket, {REVIEW_SENTIMENT_SQL} AS review_sentiment
+                 FROM review_topics rt
+                 JOIN reviews r ON r.id = rt.review_id
+                 WHERE rt.topic_id IN (SELECT value FROM json_each(?1))
+                   AND r.date >= ?2 AND r.date <= ?3 AND (?4 IS NULL OR r.region = ?4)
+                 GROUP BY r.id
+             )
+             GROUP BY bucket
+             ORDER BY bucket"
+        ))?;
+        let topic_ids = serde_json::to_string(topic_ids)?;
+        let points = stmt
+            .query_map(params![topic_ids, date_from, date_to, region], |row| {
+                Ok(TimelinePoint {
+                    date: row.get(0)?,
+                    positive: row.get(1)?,
+                    neutral: row.get(2)?,
+                    negative: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(points)
+    }
+
+    /// Страница отзывов по фильтрам и общее число подходящих отзывов
+    pub fn reviews(&self, filter: &ReviewFilter) -> Result<(Vec<ReviewItem>, i64)> {
+        let grouped = format!(
+            "SELECT r.id, r.date, r.region, r.text, {REVIEW_SENTIMENT_SQL} AS review_sentiment, r.external_id, r.upload_id
+             FROM reviews r
+             JOIN review_topics rt ON rt.review_id = r.id
+             WHERE (?1 IS NULL OR rt.topic_id = ?1)
+               AND (?2 IS NULL OR r.date >= ?2)
+               AND (?3 IS NULL OR r.date <= ?3)
+               AND (?4 IS NULL OR r.region = ?4)
+             GROUP BY r.id
+             HAVING ?5 IS NULL OR review_sentiment = ?5"
+        );
+        let sentiment = filter.sentiment.map(|s| s.as_str());
+        let offset = (filter.page - 1) * filter.limit;
+
+        let conn = self.conn()?;
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM ({grouped})"),
+            params![filter.topic_id, filter.date_from, filter.date_to, filter.region, sentiment],
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = conn.prepare(&format!("{grouped} ORDER BY r.date DESC, r.id DESC LIMIT ?6 OFFSET ?7"))?;
+        let reviews = stmt
+            .query_map(
+                params![filter.topic_id, filter.date_from, filter.date_to, filter.region, sentiment, filter.limit, offset],
+                |row| {
+                    Ok(ReviewItem {
+                        id: row.get(0)?,
+                        date: row.get(1)?,
+                        region: row.get(2)?,
+                        text: row.get(3)?,
+                        sentiment: row.get(4)?,
+                        external_id: row.get(5)?,
+                        upload_id: row.get(6)?,
+                    })
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok((reviews, total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic_id(store: &ReviewStore, name: &str) -> Option<i32> {
+        store.taxonomy().unwrap().topics().iter().find(|t| t.name == name).map(|t| t.id)
+    }
+
+    #[test]
+    fn new_database_gets_seed_taxonomy() {
+        let store = ReviewStore::open(Path::new(":memory:")).unwrap();
+        let taxonomy = store.taxonomy().unwrap();
+        let cards = taxonomy.resolve("кредитка").expect("credit cards topic");
+        assert_eq!(cards.name, "Кредитные карты");
+        assert_eq!(cards.parent_id, topic_id(&store, "Карты"));
+        assert_eq!(taxonomy.resolve("Карта").map(|t| t.name.as_str()), Some("Карты"));
+    }
+
+    #[test]
+    fn legacy_topic_is_merged_into_taxonomy_topic() {
+        // База до таксономии: топики создавались по меткам модели
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE topics (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL UNIQUE);
+             CREATE TABLE reviews (id INTEGER PRIMARY KEY, date TEXT NOT NULL, region TEXT NOT NULL DEFAULT '', text TEXT NOT NULL);
+             CREATE TABLE review_topics (
+                 review_id INTEGER NOT NULL REFERENCES reviews(id) ON DELETE CASCADE,
+                 topic_id  INTEGER NOT NULL REFERENCES topics(id),
+                 sentiment TEXT NOT NULL,
+                 PRIMARY KEY (review_id, topic_id)
+             );
+             INSERT INTO topics (id, name) VALUES (1, 'Карта'), (2, 'Ипотека'), (3, 'Карты');
+             INSERT INTO reviews (id, date, text) VALUES
+                 (10, '2024-01-01', 'карта'), (11, '2024-01-02', 'ипотека и карта'), (12, '2024-01-03', 'карты');
+             INSERT INTO review_topics VALUES
+                 (10, 1, 'negative'), (11, 1, 'positive'), (11, 2, 'neutral'), (12, 1, 'neutral'), (12, 3, 'positive');",
+        )
+        .unwrap();
+        let store = ReviewStore::from_connection(conn).unwrap();
+
+        let cards = topic_id(&store, "Карты").unwrap();
+        assert_eq!(topic_id(&store, "Карта"), None);
+        assert_eq!(store.taxonomy().unwrap().resolve("Карта").map(|t| t.id), Some(cards));
+
+        let (reviews, total) = store.reviews(&filter(Some(cards))).unwrap();
+        assert_eq!(total, 3);
+        // Прежний id отзыва сохраняется как внешний
+        let sentiments: Vec<_> = reviews.iter().map(|r| (r.external_id, r.sentiment.as_str())).collect();
+        assert_eq!(sentiments, [(12, "positive"), (11, "positive"), (10, "negative")]);
+        let mortgage = topic_id(&store, "Ипотека").unwrap();
+        assert_eq!(store.taxonomy().unwrap().get(mortgage).unwrap().parent_id, topic_id(&store, "Кредиты"));
+    }
+
+    fn filter(topic_id: Option<i32>) -> ReviewFilter {
+        ReviewFilter { topic_id, sentiment: None, date_from: None, date_to: None, region: None, page: 1, limit: 50 }
+    }
+
+    fn review(id: i64, date: &str, region: &str, topics: &[(&str, Sentiment)]) -> NewReview {
+        let topics = topics.iter().map(|(t, s)| (t.to_string(), *s)).collect();
+        NewReview { id, date: date.to_string(), region: region.to_string(), text: format!("отзыв {id}"), topics }
+    }
+
+    /// Три отзыва: 1 — Карты и Ипотека разной тональности, 2 — Карты в другом регионе, 3 — Ипотека
+    fn sample_store() -> ReviewStore {
+        let store = ReviewStore::open(Path::new(":memory:")).unwrap();
+        let reviews = [
+            review(1, "2024-01-03", "Москва", &[("Карта", Sentiment::Positive), ("Ипотека", Sentiment::Negative)]),
+            review(2, "2024-01-07", "Казань", &[("Карты", Sentiment::Positive)]),
+            review(3, "2024-01-08", "Москва", &[("Ипотека", Sentiment::Neutral)]),
+        ];
+        store.save_upload("reviews.json", &reviews, "{}").unwrap();
+        store
+    }
+
+    #[test]
+    fn uploads_with_same_external_ids_are_kept_apart() {
+        let store = ReviewStore::open(Path::new(":memory:")).unwrap();
+        let upload = |name: &str, date: &str, sentiment: Sentiment| {
+            store.save_upload(name, &[review(1, date, "", &[("Карты", sentiment)])], name).unwrap()
+        };
+        let first = upload("a.json", "2024-01-01", Sentiment::Positive);
+        let second = upload("b.json", "2024-01-02", Sentiment::Negative);
+
+        let (reviews, total) = store.reviews(&filter(None)).unwrap();
+        assert_eq!(total, 2);
+        let keys: Vec<_> = reviews.iter().map(|r| (r.upload_id, r.external_id, r.sentiment.as_str())).collect();
+        assert_eq!(keys, [(Some(second), 1, "negative"), (Some(first), 1, "positive")]);
+        assert_eq!(store.upload_payload(Some(first)).unwrap(), Some(("a.json".to_string(), "a.json".to_string())));
+    }
+
+    #[test]
+    fn total_counts_all_filtered_reviews_not_the_page() {
+        let store = sample_store();
+        let cards = topic_id(&store, "Карты");
+        let mortgage = topic_id(&store, "Ипотека");
+
+        let (page, total) = store.reviews(&ReviewFilter { limit: 1, ..filter(None) }).unwrap();
+        assert_eq!((page.len(), total), (1, 3));
+        let (page, total) = store.reviews(&ReviewFilter { page: 2, limit: 2, ..filter(None) }).unwrap();
+        assert_eq!((page.len(), total), (1, 3));
+
+        assert_eq!(store.reviews(&filter(cards)).unwrap().1, 2);
+        let region = ReviewFilter { region: Some("Москва".to_string()), ..filter(mortgage) };
+        assert_eq!(store.reviews(&region).unwrap().1, 2);
+        let dates =
+            ReviewFilter { date_from: Some("2024-01-04".into()), date_to: Some("2024-01-07".into()), ..filter(None) };
+        let (page, total) = store.reviews(&dates).unwrap();
+        assert_eq!((page[0].external_id, total), (2, 1));
+    }
+
+    #[test]
+    fn sentiment_filter_uses_whole_review_sentiment() {
+        let store = sample_store();
+        let cards = topic_id(&store, "Карты");
+        let external_ids = |filter: ReviewFilter| -> (Vec<i64>, i64) {
+            let (reviews, total) = store.reviews(&filter).unwrap();
+            (reviews.iter().map(|r| r.external_id).collect(), total)
+        };
+
+        // Отрицательная тональность по ипотеке делает отрицательным весь отзыв 1
+        assert_eq!(external_ids(ReviewFilter { sentiment: Some(Sentiment::Negative), ..filter(None) }), (vec![1], 1));
+        assert_eq!(external_ids(ReviewFilter { sentiment: Some(Sentiment::Positive), ..filter(None) }), (vec![2], 1));
+        // С фильтром по топику тональность считается только по его меткам
+        let positive_cards = ReviewFilter { sentiment: Some(Sentiment::Positive), ..filter(cards) };
+        assert_eq!(external_ids(positive_cards), (vec![2, 1], 2));
+    }
+
+    #[test]
+    fn week_buckets_start_on_monday() {
+        let store = sample_store();
+        let topics = store.taxonomy().unwrap().topics().iter().map(|t| t.id).collect::<Vec<_>>();
+
+        let points = store.timeline(&topics, "2024-01-01", "2024-01-31", "week", None).unwrap();
+        let weeks: Vec<_> = points.iter().map(|p| (p.date.as_str(), p.positive, p.neutral, p.negative)).collect();
+        assert_eq!(weeks, [("2024-01-01", 1, 0, 1), ("2024-01-08", 0, 1, 0)]);
+
+        let points = store.timeline(&topics, "2024-01-01", "2024-01-31", "month", Some("Москва")).unwrap();
+        assert_eq!(points.iter().map(|p| p.date.as_str()).collect::<Vec<_>>(), ["2024-01-01"]);
+        assert!(store.timeline(&topics, "2024-01-01", "2024-01-31", "year", None).is_err());
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+
+use crate::domain::{Sentiment, SentimentStats, Topic, TopicInput, TopicsStatsItem};
+
+/// Ошибка изменения таксономии, которую должен исправить клиент
+#[derive(Debug, thiserror::Error)]
+pub enum TaxonomyError {
+    #[error("topic {0} not found")]
+    NotFound(i32),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0}")]
+    Conflict(String),
+}
+
+impl ResponseError for TaxonomyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            TaxonomyError::NotFound(_) => StatusCode::NOT_FOUND,
+            TaxonomyError::Invalid(_) => StatusCode::BAD_REQUEST,
+            TaxonomyError::Conflict(_) => StatusCode::CONFLICT,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(serde_json::json!({ "error": self.to_string() }))
+    }
+}
+
+/// Снимок таксономии топиков с поиском по имени и синонимам без учёта регистра
+#[derive(Debug, Default)]
+pub struct Taxonomy {
+    topics: Vec<Topic>,
+    by_id: HashMap<i32, usize>,
+    by_label: HashMap<String, usize>,
+}
+
+impl Taxonomy {
+    pub fn new(topics: Vec<Topic>) -> Self {
+        let by_id = topics.iter().enumerate().map(|(i, t)| (t.id, i)).collect();
+        let mut by_label = HashMap::new();
+        for (i, topic) in topics.iter().enumerate() {
+            for label in std::iter::once(&topic.name).chain(&topic.synonyms) {
+                by_label.entry(normalize(label)).or_insert(i);
+            }
+        }
+        Self { topics, by_id, by_label }
+    }
+
+    /// Все топики в порядке id
+    pub fn topics(&self) -> &[Topic] {
+        &self.topics
+    }
+
+    pub fn get(&self, id: i32) -> Option<&Topic> {
+        self.by_id.get(&id).map(|&i| &self.topics[i])
+    }
+
+    /// Топик по имени или синониму
+    pub fn resolve(&self, label: &str) -> Option<&Topic> {
+        self.by_label.get(&normalize(label)).map(|&i| &self.topics[i])
+    }
+
+    /// Путь от корня до топика включительно
+    pub fn path(&self, id: i32) -> Vec<&Topic> {
+        let mut path = Vec::new();
+        let mut current = self.get(id);
+        // Длина пути ограничена числом топиков на случай цикла в базе
+        while let Some(topic) = current.filter(|_| path.len() < self.topics.len()) {
+            path.push(topic);
+            current = topic.parent_id.and_then(|parent| self.get(parent));
+        }
+        path.reverse();
+        path
+    }
+
+    /// Топик и все его потомки
+    pub fn subtree(&self, id: i32) -> Vec<i32> {
+        self.topics.iter().filter(|t| self.path(t.id).iter().any(|a| a.id == id)).map(|t| t.id).collect()
+    }
+
+    /// Статистика деревом: каждый топик включает отзывы потомков, уровни глубже `depth`
+    /// сворачиваются в предка; отзыв считается в узле один раз
+    ///
+    /// `labels` — тройки (топик, отзыв, тональность).
+    pub fn rollup_stats(&self, labels: &[(i32, i64, Sentiment)], depth: Option<usize>) -> Vec<TopicsStatsItem> {
+        let mut reviews: BTreeMap<i32, HashMap<i64, Sentiment>> = BTreeMap::new();
+        for &(topic_id, review_id, sentiment) in labels {
+            let path = self.path(topic_id);
+            let shown = depth.map_or(path.len(), |depth| path.len().min(depth + 1));
+            for node in &path[..shown] {
+                reviews
+                    .entry(node.id)
+                    .or_default()
+                    .entry(review_id)
+                    .and_modify(|s| *s = review_sentiment(*s, sentiment))
+                    .or_insert(sentiment);
+            }
+        }
+
+        let mut children: BTreeMap<Option<i32>, Vec<i32>> = BTreeMap::new();
+        for &id in reviews.keys() {
+            let parent = self.get(id).and_then(|t| t.parent_id).filter(|p| reviews.contains_key(p));
+            children.entry(parent).or_default().push(id);
+        }
+        self.stats_tree(None, &children, &reviews)
+    }
+
+    fn stats_tree(
+        &self,
+        parent: Option<i32>,
+        children: &BTreeMap<Option<i32>, Vec<i32>>,
+        reviews: &BTreeMap<i32, HashMap<i64, Sentiment>>,
+    ) -> Vec<TopicsStatsItem> {
+        let Some(ids) = children.get(&parent) else { return Vec::new() };
+        ids.iter()
+            .filter_map(|&id| {
+                let topic = self.get(id)?;
+                let mut stats = SentimentStats { positive: 0, neutral: 0, negative: 0 };
+                for sentiment in reviews[&id].values() {
+                    match sentiment {
+                        Sentiment::Positive => stats.positive += 1,
+                        Sentiment::Neutral => stats.neutral += 1,
+                        Sentiment::Negative => stats.negative += 1,
+                    }
+                }
+                Some(TopicsStatsItem {
+                    id,
+                    name: topic.name.clone(),
+                    stats,
+                    children: self.stats_tree(Some(id), children, reviews),
+                })
+            })
+            .collect()
+    }
+
+    /// Проверяет топик перед созданием (`id = None`) или заменой; возвращает его с очищенными метками
+    pub fn validate(&self, id: Option<i32>, input: &TopicInput) -> Result<TopicInput, TaxonomyError> {
+        let name = input.name.trim().to_string();
+        if name.is_empty() {
+            return Err(TaxonomyError::Invalid("topic name must not be empty".to_string()));
+        }
+        let mut synonyms: Vec<String> = Vec::with_capacity(input.synonyms.len());
+        for synonym in input.synonyms.iter().map(|s| s.trim()) {
+            if synonym.is_empty() {
+                return Err(TaxonomyError::Invalid("topic synonyms must not be empty".to_string()));
+            }
+            let key = normalize(synonym);
+            if key != normalize(&name) && !synonyms.iter().any(|s| normalize(s) == key) {
+                synonyms.push(synonym.to_string());
+            }
+        }
+
+        for label in std::iter::once(&name).chain(&synonyms) {
+            if let Some(other) = self.resolve(label).filter(|t| Some(t.id) != id) {
+                return Err(TaxonomyError::Conflict(format!("label {:?} already belongs to topic {} ({})", label, other.id, other.name)));
+            }
+        }
+
+        if let Some(parent_id) = input.parent_id {
+            // Поднимаемся от родителя к корню: встретить сам топик — значит получить цикл
+            let mut ancestor = Some(parent_id);
+            while let Some(current) = ancestor {
+                if Some(current) == id {
+                    return Err(TaxonomyError::Invalid(format!("topic {} cannot be a descendant of itself", current)));
+                }
+                let topic = self
+                    .get(current)
+                    .ok_or_else(|| TaxonomyError::Invalid(format!("parent topic {} not found", current)))?;
+                ancestor = topic.parent_id;
+            }
+        }
+
+        Ok(TopicInput { name, parent_id: input.parent_id, synonyms, active: input.active })
+    }
+}
+
+/// Тональность отзыва по нескольким топикам: отрицательная имеет приоритет, затем положительная
+fn review_sentiment(a: Sentiment, b: Sentiment) -> Sentiment {
+    match (a, b) {
+        (Sentiment::Negative, _) | (_, Sentiment::Negative) => Sentiment::Negative,
+        (Sentiment::Positive, _) | (_, Sentiment::Positive) => Sentiment::Positive,
+        _ => Sentiment::Neutral,
+    }
+}
+
+fn normalize(label: &str) -> String {
+    label.trim().to_lowercase()
+}
+
+/// Актуальная таксономия: изменения через `/topics` подменяют снимок атомарно
+pub struct SharedTaxonomy {
+    current: RwLock<Arc<Taxonomy>>,
+    /// Изменения и перечитывание снимка идут по одному, чтобы старый снимок не подменил новый
+    updates: Mutex<()>,
+}
+
+impl SharedTaxonomy {
+    pub fn new(taxonomy: Taxonomy) -> Self {
+        Self { current: RwLock::new(Arc::new(taxonomy)), updates: Mutex::new(()) }
+    }
+
+    /// Удерживается на время изменения таксономии в хранилище и `replace`
+    pub fn lock_updates(&self) -> MutexGuard<'_, ()> {
+        self.updates.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn current(&self) -> Arc<Taxonomy> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn replace(&self, taxonomy: Taxonomy) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(taxonomy);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic(id: i32, name: &str, parent_id: Option<i32>, synonyms: &[&str]) -> Topic {
+        let TopicInput { name, synonyms, .. } = input(name, parent_id, synonyms);
+        Topic { id, name, parent_id, synonyms, active: true }
+    }
+
+    fn input(name: &str, parent_id: Option<i32>, synonyms: &[&str]) -> TopicInput {
+        let synonyms = synonyms.iter().map(|s| s.to_string()).collect();
+        TopicInput { name: name.to_string(), parent_id, synonyms, active: true }
+    }
+
+    /// Кредиты -> Ипотека -> Льготная ипотека, Карты отдельно
+    fn taxonomy() -> Taxonomy {
+        Taxonomy::new(vec![
+            topic(1, "Кредиты", None, &["Кредит"]),
+            topic(2, "Ипотека", Some(1), &[]),
+            topic(3, "Льготная ипотека", Some(2), &[]),
+            topic(4, "Карты", None, &["Карта"]),
+        ])
+    }
+
+    #[test]
+    fn topic_cannot_be_its_own_parent() {
+        let result = taxonomy().validate(Some(2), &input("Ипотека", Some(2), &[]));
+        assert!(matches!(result, Err(TaxonomyError::Invalid(_))));
+    }
+
+    #[test]
+    fn topic_cannot_move_under_its_descendant() {
+        let result = taxonomy().validate(Some(1), &input("Кредиты", Some(3), &[]));
+        assert!(matches!(result, Err(TaxonomyError::Invalid(_))));
+        // Под другую ветку — можно
+        assert!(taxonomy().validate(Some(2), &input("Ипотека", Some(4), &[])).is_ok());
+    }
+
+    #[test]
+    fn parent_must_exist() {
+        let result = taxonomy().validate(None, &input("Вклады", Some(42), &[]));
+        assert!(matches!(result, Err(TaxonomyError::Invalid(_))));
+    }
+
+    #[test]
+    fn labels_of_other_topics_conflict() {
+        let taxonomy = taxonomy();
+        let result = taxonomy.validate(None, &input("Кредитные карты", Some(4), &[" карта "]));
+        assert!(matches!(result, Err(TaxonomyError::Conflict(_))));
+        let result = taxonomy.validate(Some(2), &input("кредиты", Some(1), &[]));
+        assert!(matches!(result, Err(TaxonomyError::Conflict(_))));
+        // Собственные метки топика не конфликтуют
+        assert!(taxonomy.validate(Some(4), &input("Карты", None, &["Карта"])).is_ok());
+    }
+
+    #[test]
+    fn blank_labels_are_rejected() {
+        let taxonomy = taxonomy();
+        assert!(matches!(taxonomy.validate(None, &input("  ", None, &[])), Err(TaxonomyError::Invalid(_))));
+        assert!(matches!(taxonomy.validate(None, &input("Вклады", None, &[" "])), Err(TaxonomyError::Invalid(_))));
+    }
+
+    #[test]
+    fn synonyms_are_trimmed_and_deduplicated() {
+        let cleaned = taxonomy()
+            .validate(None, &input(" Вклады ", None, &["Вклад", " вклад", "ВКЛАДЫ", "Депозит"]))
+            .expect("valid topic");
+        assert_eq!(cleaned.name, "Вклады");
+        assert_eq!(cleaned.synonyms, ["Вклад", "Депозит"]);
+    }
+
+    fn counts(item: &TopicsStatsItem) -> (i64, i64, i64) {
+        (item.stats.positive, item.stats.neutral, item.stats.negative)
+    }
+
+    #[test]
+    fn subtree_contains_topic_and_descendants() {
+        let taxonomy = taxonomy();
+        assert_eq!(taxonomy.subtree(1), [1, 2, 3]);
+        assert_eq!(taxonomy.subtree(3), [3]);
+        assert!(taxonomy.subtree(42).is_empty());
+    }
+
+    #[test]
+    fn review_shared_by_siblings_is_counted_once_in_parent() {
+        let taxonomy = Taxonomy::new(vec![
+            topic(1, "Карты", None, &[]),
+            topic(2, "Кредитные карты", Some(1), &[]),
+            topic(3, "Дебетовые карты", Some(1), &[]),
+        ]);
+        let labels = [(2, 10, Sentiment::Positive), (3, 10, Sentiment::Negative), (3, 11, Sentiment::Neutral)];
+        let stats = taxonomy.rollup_stats(&labels, None);
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(counts(&stats[0]), (0, 1, 1));
+        let children: Vec<_> = stats[0].children.iter().map(|c| (c.id, counts(c))).collect();
+        assert_eq!(children, [(2, (1, 0, 0)), (3, (0, 1, 1))]);
+    }
+
+    #[test]
+    fn levels_below_depth_are_folded_into_ancestor() {
+        let labels = [(3, 10, Sentiment::Negative), (2, 11, Sentiment::Positive), (4, 12, Sentiment::Neutral)];
+        let stats = taxonomy().rollup_stats(&labels, Some(0));
+
+        let roots: Vec<_> = stats.iter().map(|s| (s.id, counts(s), s.children.len())).collect();
+        assert_eq!(roots, [(1, (1, 0, 1), 0), (4, (0, 1, 0), 0)]);
+
+        let stats = taxonomy().rollup_stats(&labels, Some(1));
+        assert_eq!(counts(&stats[0]), (1, 0, 1));
+        assert_eq!(stats[0].children.len(), 1);
+        assert_eq!(counts(&stats[0].children[0]), (1, 0, 1));
+        assert!(stats[0].children[0].children.is_empty());
+    }
+
+    #[test]
+    fn parent_cycle_does_not_hang() {
+        let taxonomy = Taxonomy::new(vec![
+            topic(1, "Кредиты", Some(2), &[]),
+            topic(2, "Ипотека", Some(1), &[]),
+            topic(3, "Карты", None, &[]),
+        ]);
+        assert!(taxonomy.path(1).len() <= taxonomy.topics().len());
+        assert_eq!(taxonomy.subtree(1), [1, 2]);
+
+        let stats = taxonomy.rollup_stats(&[(1, 10, Sentiment::Negative), (3, 11, Sentiment::Positive)], None);
+        assert_eq!(stats.iter().map(|s| s.id).collect::<Vec<_>>(), [3]);
+    }
+}
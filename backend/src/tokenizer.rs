@@ -1,87 +1,331 @@
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 
-/// Простой токенизатор для русского текста
-/// В реальном проекте здесь должна быть интеграция с библиотекой токенизации
-#[allow(dead_code)]
-pub struct SimpleTokenizer {
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// WordPiece токенизатор (BERT-совместимый), словарь загружается из `MODEL_DIR`
+///
+/// Поддерживаются `tokenizer.json` (HuggingFace, модель WordPiece) и `vocab.txt`.
+pub struct WordPieceTokenizer {
     vocab: HashMap<String, u32>,
+    unk_id: u32,
+    cls_id: u32,
+    sep_id: u32,
+    pad_id: u32,
+    continuing_prefix: String,
+    max_input_chars_per_word: usize,
+    lowercase: bool,
+    strip_accents: bool,
     max_length: usize,
 }
 
-impl SimpleTokenizer {
-    #[allow(dead_code)]
-    pub fn new() -> Self {
-        let mut vocab = HashMap::new();
-        
-        // Базовые токены
-        vocab.insert("[PAD]".to_string(), 0);
-        vocab.insert("[UNK]".to_string(), 1);
-        vocab.insert("[CLS]".to_string(), 2);
-        vocab.insert("[SEP]".to_string(), 3);
-        
-        // Простой словарь на основе частых слов
-        let common_words = [
-            "и", "в", "не", "на", "с", "по", "для", "от", "до", "из", "к", "о", "у", "за", "при",
-            "обслуживание", "банк", "карта", "кредит", "ипотека", "вклад", "мобильный", "приложение",
-            "онлайн", "сайт", "терминал", "поддержка", "хорошо", "плохо", "быстро", "медленно",
-            "понравилось", "непонравилось", "рекомендую", "удобно", "зависает", "работает"
-        ];
-        
-        for (i, word) in common_words.iter().enumerate() {
-            vocab.insert(word.to_string(), (i + 4) as u32);
+/// Батч токенизированных текстов, дополненный до самой длинной последовательности
+pub struct EncodedBatch {
+    pub input_ids: Vec<i64>,
+    pub attention_mask: Vec<i64>,
+    pub batch_size: usize,
+    pub seq_len: usize,
+}
+
+impl WordPieceTokenizer {
+    /// Ищет `tokenizer.json`, затем `vocab.txt` в каталоге модели
+    pub fn from_model_dir(dir: &Path, lowercase: bool) -> Result<Self> {
+        let tokenizer_json = dir.join("tokenizer.json");
+        if tokenizer_json.exists() {
+            return Self::from_tokenizer_json(&tokenizer_json);
         }
-        
-        Self {
-            vocab,
-            max_length: 512,
+        let vocab_txt = dir.join("vocab.txt");
+        if vocab_txt.exists() {
+            return Self::from_vocab_file(&vocab_txt, lowercase);
         }
+        bail!("neither tokenizer.json nor vocab.txt found in {:?}", dir)
     }
-    
-    /// Токенизирует текст и возвращает массив индексов токенов
-    #[allow(dead_code)]
-    pub fn tokenize(&self, text: &str) -> Vec<u32> {
-        let mut tokens = vec![self.vocab["[CLS]"]];
-        
-        // Простая токенизация по словам и знакам препинания
-        let words: Vec<&str> = text
-            .split_whitespace()
-            .flat_map(|word| {
-                // Разделяем по знакам препинания
-                word.split_inclusive(|c: char| c.is_ascii_punctuation())
-                    .filter(|s| !s.is_empty())
-            })
+
+    /// `vocab.txt`: один токен на строку, id = номер строки
+    pub fn from_vocab_file(path: &Path, lowercase: bool) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read vocab {:?}", path))?;
+        let vocab: HashMap<String, u32> = content
+            .lines()
+            .enumerate()
+            .map(|(i, token)| (token.trim_end_matches('\r').to_string(), i as u32))
             .collect();
-        
-        for word in words.iter().take(self.max_length - 2) {
-            let word_lower = word.to_lowercase();
-            let token = self.vocab.get(&word_lower)
-                .or_else(|| self.vocab.get(&word.to_string()))
-                .unwrap_or(&self.vocab["[UNK]"]);
-            tokens.push(*token);
+
+        Self::build(vocab, "[UNK]", "##".to_string(), 100, (lowercase, lowercase), 512)
+    }
+
+    /// HuggingFace `tokenizer.json` с моделью WordPiece и `BertNormalizer`
+    pub fn from_tokenizer_json(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read tokenizer {:?}", path))?;
+        let json: Value = serde_json::from_str(&content)
+            .with_context(|| format!("invalid JSON in {:?}", path))?;
+
+        let model = json.get("model").ok_or_else(|| anyhow!("tokenizer.json has no `model`"))?;
+        let model_type = model.get("type").and_then(|v| v.as_str()).unwrap_or("WordPiece");
+        if model_type != "WordPiece" {
+            bail!("unsupported tokenizer model type `{}`, only WordPiece is supported", model_type);
         }
-        
-        tokens.push(self.vocab["[SEP]"]);
-        
-        // Дополняем до максимальной длины
-        while tokens.len() < self.max_length {
-            tokens.push(self.vocab["[PAD]"]);
+
+        let vocab: HashMap<String, u32> = model
+            .get("vocab")
+            .and_then(|v| v.as_object())
+            .ok_or_else(|| anyhow!("tokenizer.json has no `model.vocab`"))?
+            .iter()
+            .filter_map(|(token, id)| Some((token.clone(), id.as_u64()? as u32)))
+            .collect();
+
+        let unk_token = model.get("unk_token").and_then(|v| v.as_str()).unwrap_or("[UNK]");
+        let continuing_prefix = model
+            .get("continuing_subword_prefix")
+            .and_then(|v| v.as_str())
+            .unwrap_or("##")
+            .to_string();
+        let max_input_chars_per_word = model
+            .get("max_input_chars_per_word")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(100) as usize;
+
+        let normalization = normalizer_flags(json.get("normalizer"));
+        let max_length = json
+            .get("truncation")
+            .and_then(|t| t.get("max_length"))
+            .and_then(|v| v.as_u64())
+            .unwrap_or(512) as usize;
+
+        Self::build(vocab, unk_token, continuing_prefix, max_input_chars_per_word, normalization, max_length)
+    }
+
+    fn build(
+        vocab: HashMap<String, u32>,
+        unk_token: &str,
+        continuing_prefix: String,
+        max_input_chars_per_word: usize,
+        (lowercase, strip_accents): (bool, bool),
+        max_length: usize,
+    ) -> Result<Self> {
+        let id = |token: &str| {
+            vocab
+                .get(token)
+                .copied()
+                .ok_or_else(|| anyhow!("special token {} is missing from vocab", token))
+        };
+
+        Ok(Self {
+            unk_id: id(unk_token)?,
+            cls_id: id("[CLS]")?,
+            sep_id: id("[SEP]")?,
+            pad_id: id("[PAD]")?,
+            vocab,
+            continuing_prefix,
+            max_input_chars_per_word,
+            lowercase,
+            strip_accents,
+            max_length: max_length.max(2),
+        })
+    }
+
+    pub fn vocab_size(&self) -> usize {
+        self.vocab.len()
+    }
+
+    /// Токенизирует текст без паддинга: `[CLS] ... [SEP]`, не длиннее `max_length`
+    pub fn encode(&self, text: &str) -> Vec<u32> {
+        let mut tokens = vec![self.cls_id];
+
+        'words: for word in self.basic_tokenize(text) {
+            for id in self.wordpiece(&word) {
+                if tokens.len() >= self.max_length - 1 {
+                    break 'words;
+                }
+                tokens.push(id);
+            }
         }
-        
-        tokens.truncate(self.max_length);
+
+        tokens.push(self.sep_id);
         tokens
     }
-    
-    /// Создает маску внимания (1 для реальных токенов, 0 для padding)
-    #[allow(dead_code)]
-    pub fn create_attention_mask(&self, tokens: &[u32]) -> Vec<u32> {
-        tokens.iter()
-            .map(|&token| if token == self.vocab["[PAD]"] { 0 } else { 1 })
-            .collect()
+
+    /// Нормализация и разбиение на слова: по пробелам, затем по пунктуации
+    /// (включая «ёлочки», „лапки“, тире и многоточие), каждый знак — отдельное слово
+    fn basic_tokenize(&self, text: &str) -> Vec<String> {
+        let cleaned: String = text
+            .chars()
+            .filter(|&c| c != '\u{0}' && c != '\u{fffd}' && (!c.is_control() || c.is_whitespace()))
+            .collect();
+
+        let mut words = Vec::new();
+        for raw in cleaned.split_whitespace() {
+            let mut word = if self.lowercase { raw.to_lowercase() } else { raw.to_string() };
+            if self.strip_accents {
+                word = word.nfd().filter(|&c| !is_combining_mark(c)).collect();
+            }
+
+            let mut current = String::new();
+            for c in word.chars() {
+                if is_punctuation(c) {
+                    if !current.is_empty() {
+                        words.push(std::mem::take(&mut current));
+                    }
+                    words.push(c.to_string());
+                } else {
+                    current.push(c);
+                }
+            }
+            if !current.is_empty() {
+                words.push(current);
+            }
+        }
+        words
+    }
+
+    /// Жадное разбиение слова на самые длинные подслова из словаря
+    fn wordpiece(&self, word: &str) -> Vec<u32> {
+        let chars: Vec<char> = word.chars().collect();
+        if chars.len() > self.max_input_chars_per_word {
+            return vec![self.unk_id];
+        }
+
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let mut end = chars.len();
+            let mut found = None;
+            while start < end {
+                let mut candidate: String = chars[start..end].iter().collect();
+                if start > 0 {
+                    candidate.insert_str(0, &self.continuing_prefix);
+                }
+                if let Some(&id) = self.vocab.get(&candidate) {
+                    found = Some(id);
+                    break;
+                }
+                end -= 1;
+            }
+            match found {
+                Some(id) => pieces.push(id),
+                None => return vec![self.unk_id],
+            }
+            start = end;
+        }
+        pieces
+    }
+
+    /// Дополняет последовательности `[PAD]`-ами до самой длинной в батче и строит маску внимания
+    /// (динамический паддинг вместо фиксированных `max_length` токенов)
+    pub fn pad_batch(&self, sequences: &[&[u32]]) -> EncodedBatch {
+        let pad = self.pad_id as i64;
+        let seq_len = sequences.iter().map(|s| s.len()).max().unwrap_or(0);
+        let batch_size = sequences.len();
+
+        let mut input_ids = Vec::with_capacity(batch_size * seq_len);
+        let mut attention_mask = Vec::with_capacity(batch_size * seq_len);
+        for seq in sequences {
+            let row_end = input_ids.len() + seq_len;
+            input_ids.extend(seq.iter().map(|&t| t as i64));
+            input_ids.resize(row_end, pad);
+            attention_mask.resize(row_end - seq_len + seq.len(), 1);
+            attention_mask.resize(row_end, 0);
+        }
+
+        EncodedBatch { input_ids, attention_mask, batch_size, seq_len }
+    }
+}
+
+/// Флаги (lowercase, strip_accents) из секции `normalizer` tokenizer.json
+fn normalizer_flags(normalizer: Option<&Value>) -> (bool, bool) {
+    let Some(normalizer) = normalizer.filter(|n| !n.is_null()) else {
+        return (false, false);
+    };
+
+    match normalizer.get("type").and_then(|v| v.as_str()) {
+        Some("BertNormalizer") => {
+            let lowercase = normalizer.get("lowercase").and_then(|v| v.as_bool()).unwrap_or(true);
+            // strip_accents = null означает "как lowercase" (поведение BERT)
+            let strip_accents = normalizer
+                .get("strip_accents")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(lowercase);
+            (lowercase, strip_accents)
+        }
+        Some("Lowercase") => (true, false),
+        Some("StripAccents") => (false, true),
+        Some("Sequence") => normalizer
+            .get("normalizers")
+            .and_then(|v| v.as_array())
+            .map(|items| {
+                items.iter().fold((false, false), |acc, n| {
+                    let (l, s) = normalizer_flags(Some(n));
+                    (acc.0 || l, acc.1 || s)
+                })
+            })
+            .unwrap_or((false, false)),
+        _ => (false, false),
     }
 }
 
-impl Default for SimpleTokenizer {
-    fn default() -> Self {
-        Self::new()
+/// Пунктуация в духе BERT: ASCII-знаки и основные блоки Unicode-пунктуации
+/// (кавычки «» „“, тире, многоточие и т.п.), которых нет в `is_ascii_punctuation`
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation()
+        || matches!(c, '\u{00A1}' | '\u{00A7}' | '\u{00AB}' | '\u{00B6}' | '\u{00B7}' | '\u{00BB}' | '\u{00BF}')
+        || ('\u{2010}'..='\u{2027}').contains(&c)
+        || ('\u{2030}'..='\u{205E}').contains(&c)
+        || ('\u{3000}'..='\u{303F}').contains(&c)
+        || ('\u{FE30}'..='\u{FE4F}').contains(&c)
+        || ('\u{FF01}'..='\u{FF0F}').contains(&c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VOCAB: [&str; 14] =
+        ["[PAD]", "[UNK]", "[CLS]", "[SEP]", "«", "»", "—", "…", "банк", "хорош", "##ий", "##ая", "при", "##ложение"];
+
+    /// Без strip_accents: NFD превратил бы «й» в «и»
+    fn tokenizer(max_length: usize) -> WordPieceTokenizer {
+        let vocab = VOCAB.iter().enumerate().map(|(i, t)| (t.to_string(), i as u32)).collect();
+        WordPieceTokenizer::build(vocab, "[UNK]", "##".to_string(), 100, (true, false), max_length).expect("valid vocab")
+    }
+
+    fn tokens(tokenizer: &WordPieceTokenizer, text: &str) -> Vec<&'static str> {
+        tokenizer.encode(text).into_iter().map(|id| VOCAB[id as usize]).collect()
+    }
+
+    #[test]
+    fn quotes_dashes_and_ellipsis_are_split_from_words() {
+        let tokenizer = tokenizer(512);
+        assert_eq!(tokenizer.basic_tokenize("«Банк»—хороший…"), ["«", "банк", "»", "—", "хороший", "…"]);
+        assert_eq!(tokens(&tokenizer, "«Банк»—хороший…"), ["[CLS]", "«", "банк", "»", "—", "хорош", "##ий", "…", "[SEP]"]);
+    }
+
+    #[test]
+    fn words_are_split_into_longest_subwords() {
+        let tokenizer = tokenizer(512);
+        assert_eq!(tokens(&tokenizer, "Приложение хорошая"), ["[CLS]", "при", "##ложение", "хорош", "##ая", "[SEP]"]);
+        // Слово, которое не собирается из словаря целиком, — один [UNK]
+        assert_eq!(tokens(&tokenizer, "хорошо банк"), ["[CLS]", "[UNK]", "банк", "[SEP]"]);
+    }
+
+    #[test]
+    fn long_texts_are_truncated_before_sep() {
+        let tokenizer = tokenizer(5);
+        assert_eq!(tokens(&tokenizer, "банк хороший банк банк"), ["[CLS]", "банк", "хорош", "##ий", "[SEP]"]);
+        assert_eq!(tokenizer.encode("банк банк банк банк банк").len(), 5);
+    }
+
+    #[test]
+    fn batch_is_padded_to_longest_sequence() {
+        let tokenizer = tokenizer(512);
+        let batch = tokenizer.pad_batch(&[&[2, 8, 3], &[2, 3], &[2, 9, 10, 8, 3]]);
+        assert_eq!((batch.batch_size, batch.seq_len), (3, 5));
+        assert_eq!(batch.input_ids, [2, 8, 3, 0, 0, 2, 3, 0, 0, 0, 2, 9, 10, 8, 3]);
+        assert_eq!(batch.attention_mask, [1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1, 1, 1, 1, 1]);
+        assert_eq!(tokenizer.pad_batch(&[]).seq_len, 0);
     }
 }